@@ -1 +1,130 @@
+use path_macro::path;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
 
+use crate::error::{Error, ErrorKind, ResultExt};
+use crate::{utils, Launcher};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Account {
+	Offline { username: String, uuid: String },
+}
+
+impl Account {
+	/// Creates an offline account with the same UUID the vanilla server derives
+	/// for offline players.
+	#[must_use]
+	pub fn offline(username: String) -> Self {
+		let mut hash = openssl::hash::hash(
+			openssl::hash::MessageDigest::md5(),
+			format!("OfflinePlayer:{username}").as_bytes(),
+		)
+		.map_or([0; 16], |digest| {
+			let mut bytes = [0; 16];
+			bytes.copy_from_slice(&digest);
+			bytes
+		});
+		hash[6] = hash[6] & 0x0f | 0x30;
+		hash[8] = hash[8] & 0x3f | 0x80;
+
+		Self::Offline {
+			username,
+			uuid: hex::encode(hash),
+		}
+	}
+
+	#[must_use]
+	pub fn username(&self) -> &str {
+		match self {
+			Account::Offline { username, .. } => username,
+		}
+	}
+
+	#[must_use]
+	pub fn uuid(&self) -> &str {
+		match self {
+			Account::Offline { uuid, .. } => uuid,
+		}
+	}
+
+	#[must_use]
+	pub fn access_token(&self) -> &str {
+		match self {
+			Account::Offline { .. } => "0",
+		}
+	}
+
+	#[must_use]
+	pub fn user_type(&self) -> &str {
+		match self {
+			Account::Offline { .. } => "legacy",
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Accounts {
+	accounts: Vec<Account>,
+}
+
+impl Accounts {
+	pub async fn load(launcher: &Launcher) -> Result<Accounts, Error> {
+		let path = path!(launcher.data_dir() / "accounts.json");
+		match fs::read(&path).await {
+			Ok(bytes) => serde_json::from_slice(&bytes).with_path(&path),
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Accounts::default()),
+			Err(err) => Err(err).with_path(&path),
+		}
+	}
+
+	pub async fn save(&self, launcher: &Launcher) -> Result<(), Error> {
+		let path = path!(launcher.data_dir() / "accounts.json");
+		utils::write(&path, serde_json::to_string_pretty(self)?)
+			.await
+			.with_path(&path)
+	}
+
+	#[must_use]
+	pub fn list(&self) -> &Vec<Account> {
+		&self.accounts
+	}
+
+	#[must_use]
+	pub fn get(&self, username: &str) -> Option<&Account> {
+		self.accounts
+			.iter()
+			.find(|account| account.username() == username)
+	}
+
+	/// The account named `username`, else the one named `default`, else the
+	/// only account.
+	pub fn find(&self, username: Option<&str>, default: Option<&str>) -> Result<&Account, Error> {
+		match username.or(default) {
+			Some(username) => self.get(username),
+			None if self.accounts.len() == 1 => self.accounts.first(),
+			None => None,
+		}
+		.ok_or_else(|| Error::new(ErrorKind::AccountNotFound))
+	}
+
+	pub fn add(&mut self, account: Account) -> Result<(), Error> {
+		if self.get(account.username()).is_some() {
+			return Err(Error::new(ErrorKind::AccountAlreadyExists));
+		}
+
+		self.accounts.push(account);
+
+		Ok(())
+	}
+
+	pub fn remove(&mut self, username: &str) -> Result<Account, Error> {
+		let index = self
+			.accounts
+			.iter()
+			.position(|account| account.username() == username)
+			.ok_or_else(|| Error::new(ErrorKind::AccountNotFound))?;
+
+		Ok(self.accounts.remove(index))
+	}
+}
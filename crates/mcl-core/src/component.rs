@@ -1,40 +1,110 @@
 use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use async_trait::async_trait;
 use authlib_injector::AuthlibInjector;
+use custom_version::CustomVersion;
 use enum_dispatch::enum_dispatch;
+use fabric::{FabricLoader, FabricServer};
+use minecraft::log4shell::Mitigation;
 use minecraft::MinecraftClient;
+use quilt::QuiltLoader;
 use serde::{Deserialize, Serialize};
+use server::{MinecraftServer, PaperServer, PurpurServer};
 
-use crate::utils::RequestError;
+use crate::error::{Error, ErrorKind};
+use crate::lockfile::Lockfile;
+use crate::maven::Coordinate;
+use crate::{java, Launcher};
 
 pub mod authlib_injector;
+pub mod custom_version;
 pub mod fabric;
+pub mod loader;
 pub mod minecraft;
+pub mod quilt;
+pub mod server;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
 pub enum Tag {
+	/// The newest version of any type.
 	Latest,
+	/// The newest release.
 	Stable,
+	/// The newest snapshot.
+	LatestSnapshot,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+impl Tag {
+	#[must_use]
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Tag::Latest => "latest",
+			Tag::Stable => "stable",
+			Tag::LatestSnapshot => "latest-snapshot",
+		}
+	}
+}
+
+impl FromStr for Tag {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"latest" => Ok(Tag::Latest),
+			"stable" => Ok(Tag::Stable),
+			"latest-snapshot" => Ok(Tag::LatestSnapshot),
+			_ => Err(()),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum Version {
 	Tag(Tag),
+	/// What `tag` resolved to for versions released before `before`, an
+	/// RFC 3339 date or timestamp. Written as `stable@2021-12-01`.
+	Pinned {
+		tag: Tag,
+		before: String,
+	},
 	Id(String),
 }
 
-// TODO: Replace all of these error types with a single one
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-	#[error("request error: {0}")]
-	Request(#[from] RequestError),
-	#[error("reqwest error: {0}")]
-	Reqwest(#[from] reqwest::Error),
-	#[error("tagged version not found")]
-	VersionNotFound(),
+impl FromStr for Version {
+	type Err = Infallible;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if let Ok(tag) = s.parse() {
+			return Ok(Version::Tag(tag));
+		}
+
+		if let Some((tag, before)) = s.split_once('@') {
+			if let Ok(tag) = tag.parse() {
+				return Ok(Version::Pinned {
+					tag,
+					before: before.to_owned(),
+				});
+			}
+		}
+
+		Ok(Version::Id(s.to_owned()))
+	}
+}
+
+impl fmt::Display for Version {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Version::Tag(tag) => f.write_str(tag.as_str()),
+			Version::Pinned { tag, before } => write!(f, "{}@{before}", tag.as_str()),
+			Version::Id(id) => f.write_str(id),
+		}
+	}
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -44,12 +114,196 @@ pub enum Argument {
 	Pair(String, String),
 }
 
+/// Components are installed one layer after another, so later layers can
+/// override the main class, libraries and arguments of earlier ones.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layer {
+	BaseGame,
+	Loader,
+	Agent,
+	Override,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClasspathEntry {
+	/// Maven style `group:artifact:version[:classifier]` name.
+	pub name: String,
+	pub path: String,
+}
+
+/// Key libraries are deduplicated by, see [`Coordinate::key`].
+fn library_key(name: &str) -> String {
+	name.parse::<Coordinate>()
+		.map_or_else(|_| name.to_owned(), |coordinate| coordinate.key())
+}
+
+/// The log4j configuration the game is launched with.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct LoggingConfig {
+	/// JVM argument pointing log4j to the config, with a `${path}`
+	/// placeholder.
+	pub argument: String,
+	pub path: PathBuf,
+	/// Layout of the log output, like `log4j2-xml`.
+	pub type_: String,
+}
+
+/// The result of installing components, merged the way launchers merge a
+/// version JSON with the one it `inheritsFrom`.
+#[derive(Serialize, Debug)]
 pub struct State {
+	/// Set by the last component that has one.
 	pub main_class: String,
-	pub classpath: Vec<String>,
+	pub libraries: Vec<ClasspathEntry>,
 	pub variables: HashMap<String, String>,
 	pub game_arguments: Vec<Argument>,
 	pub jvm_arguments: Vec<Argument>,
+	/// Filled in by components with the exact versions and files they used.
+	pub lockfile: Lockfile,
+	/// Concrete Minecraft version id, set by the Minecraft component.
+	pub minecraft_version: Option<String>,
+	pub logging: Option<LoggingConfig>,
+	/// How the Log4Shell vulnerability of the version was mitigated, if it has
+	/// it.
+	pub log4shell_mitigation: Option<Mitigation>,
+	/// Directory of the instance being installed, for components that read
+	/// files from it.
+	#[serde(skip)]
+	pub instance_dir: PathBuf,
+}
+
+impl Default for State {
+	fn default() -> Self {
+		Self {
+			main_class: "net.minecraft.client.main.Main".to_owned(),
+			libraries: Vec::new(),
+			variables: HashMap::new(),
+			game_arguments: Vec::new(),
+			jvm_arguments: Vec::new(),
+			lockfile: Lockfile::default(),
+			minecraft_version: None,
+			logging: None,
+			log4shell_mitigation: None,
+			instance_dir: PathBuf::new(),
+		}
+	}
+}
+
+impl State {
+	/// Adds a library to the classpath. A library with the same group, artifact
+	/// and classifier added earlier is replaced in place, so the later version
+	/// wins.
+	pub fn add_library(&mut self, name: impl Into<String>, path: impl Into<String>) {
+		let entry = ClasspathEntry {
+			name: name.into(),
+			path: path.into(),
+		};
+		let key = library_key(&entry.name);
+
+		match self
+			.libraries
+			.iter_mut()
+			.find(|library| library_key(&library.name) == key)
+		{
+			Some(library) => *library = entry,
+			None => self.libraries.push(entry),
+		}
+	}
+
+	#[must_use]
+	pub fn classpath(&self) -> Vec<&str> {
+		self.libraries
+			.iter()
+			.map(|library| library.path.as_str())
+			.collect()
+	}
+
+	pub fn add_jvm_arguments(&mut self, arguments: impl IntoIterator<Item = Argument>) {
+		merge_arguments(&mut self.jvm_arguments, arguments);
+	}
+
+	pub fn add_game_arguments(&mut self, arguments: impl IntoIterator<Item = Argument>) {
+		merge_arguments(&mut self.game_arguments, arguments);
+	}
+}
+
+/// Options that take one value, so a later one replaces the earlier. Others,
+/// like `--tweakClass`, may be given several times. System properties
+/// (`-D...`) are single valued too.
+const SINGLE_VALUED: &[&str] = &[
+	"-cp",
+	"-classpath",
+	"--username",
+	"--version",
+	"--gameDir",
+	"--assetsDir",
+	"--assetIndex",
+	"--uuid",
+	"--accessToken",
+	"--clientId",
+	"--xuid",
+	"--userType",
+	"--userProperties",
+	"--versionType",
+	"--width",
+	"--height",
+	"--server",
+	"--port",
+	"--launchTarget",
+];
+
+/// Single valued JVM options written with their value attached, like `-Xmx2G`.
+const SINGLE_VALUED_PREFIXES: &[&str] = &["-Xmx", "-Xms", "-Xss"];
+
+/// What identifies `argument` if it may only be given once.
+fn single_valued_key(argument: &Argument) -> Option<&str> {
+	match argument {
+		Argument::Eq(name, _) | Argument::Pair(name, _)
+			if name.starts_with("-D") || SINGLE_VALUED.contains(&name.as_str()) =>
+		{
+			Some(name)
+		},
+		Argument::Single(value) => {
+			SINGLE_VALUED_PREFIXES
+				.iter()
+				.copied()
+				.find(|prefix| value.starts_with(prefix))
+		},
+		_ => None,
+	}
+}
+
+/// Appends `arguments`, replacing the value of single valued options that are
+/// already present and skipping exact duplicates.
+fn merge_arguments(target: &mut Vec<Argument>, arguments: impl IntoIterator<Item = Argument>) {
+	for argument in arguments {
+		if target.contains(&argument) {
+			continue;
+		}
+
+		let existing = single_valued_key(&argument).and_then(|key| {
+			target
+				.iter_mut()
+				.find(|existing| single_valued_key(existing) == Some(key))
+		});
+		match existing {
+			Some(existing) => *existing = argument,
+			None => target.push(argument),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Requirement {
+	/// Another component has to be on the instance.
+	Component(&'static str),
+	/// Another component must not be on the instance.
+	Conflicts(&'static str),
+	/// Minecraft has to be this version or newer, compared by release time.
+	MinecraftAtLeast(&'static str),
+	/// The Java runtime has to support `-javaagent`.
+	JavaAgent,
 }
 
 #[enum_dispatch]
@@ -58,11 +312,230 @@ pub struct State {
 pub enum ComponentEnum {
 	MinecraftClient,
 	AuthlibInjector,
+	FabricLoader,
+	QuiltLoader,
+	CustomVersion,
+	MinecraftServer,
+	FabricServer,
+	PaperServer,
+	PurpurServer,
 }
 
 #[async_trait(?Send)]
 #[enum_dispatch(ComponentEnum)]
 pub trait Component {
-	fn is_compatible(&self, component: &ComponentEnum);
-	async fn install(&self, output: &mut State) -> Result<(), Error>;
+	fn layer(&self) -> Layer;
+	fn requirements(&self) -> Vec<Requirement> {
+		Vec::new()
+	}
+	/// Pins tagged versions to a concrete version id. An existing pin is kept
+	/// unless `update` is set. Returns whether the pin changed.
+	/// `minecraft_version` is the resolved version of the base game, for
+	/// components whose versions depend on it.
+	async fn resolve(
+		&mut self,
+		_launcher: &Launcher,
+		_update: bool,
+		_minecraft_version: Option<&str>,
+	) -> Result<bool, Error> {
+		Ok(false)
+	}
+	async fn install(&self, launcher: &Launcher, output: &mut State) -> Result<(), Error>;
+}
+
+impl ComponentEnum {
+	#[must_use]
+	pub fn id(&self) -> &'static str {
+		match self {
+			ComponentEnum::MinecraftClient(_) => "minecraft-client",
+			ComponentEnum::AuthlibInjector(_) => "authlib-injector",
+			ComponentEnum::FabricLoader(_) => "fabric-loader",
+			ComponentEnum::QuiltLoader(_) => "quilt-loader",
+			ComponentEnum::CustomVersion(_) => "custom-version",
+			ComponentEnum::MinecraftServer(_) => "minecraft-server",
+			ComponentEnum::FabricServer(_) => "fabric-server",
+			ComponentEnum::PaperServer(_) => "paper-server",
+			ComponentEnum::PurpurServer(_) => "purpur-server",
+		}
+	}
+}
+
+/// Checks the [`Requirement`]s of every component against the whole set.
+/// `java` is the executable the instance is launched with.
+pub async fn check_requirements(
+	launcher: &Launcher,
+	components: &[ComponentEnum],
+	java: &Path,
+) -> Result<(), Error> {
+	let mut manifest = None;
+
+	for component in components {
+		for requirement in component.requirements() {
+			match requirement {
+				Requirement::Component(id) => {
+					if !components.iter().any(|other| other.id() == id) {
+						return Err(Error::with_source(
+							ErrorKind::MissingRequirement,
+							format!("{} requires {id}", component.id()),
+						));
+					}
+				},
+				Requirement::Conflicts(id) => {
+					if components.iter().any(|other| other.id() == id) {
+						return Err(Error::with_source(
+							ErrorKind::ComponentConflict,
+							format!("{} conflicts with {id}", component.id()),
+						));
+					}
+				},
+				Requirement::MinecraftAtLeast(minimum) => {
+					let Some((minecraft_version, resolved)) = components.iter().find_map(|other| {
+						match other {
+							ComponentEnum::MinecraftClient(client) => {
+								Some((&client.version, &client.resolved))
+							},
+							ComponentEnum::MinecraftServer(server) => {
+								Some((&server.version, &server.resolved))
+							},
+							_ => None,
+						}
+					}) else {
+						return Err(Error::with_source(
+							ErrorKind::MissingRequirement,
+							format!("{} requires minecraft", component.id()),
+						));
+					};
+
+					if manifest.is_none() {
+						manifest = Some(minecraft::get_manifest(launcher).await?);
+					}
+					let Some(manifest) = manifest.as_ref() else {
+						continue;
+					};
+
+					let version = match resolved {
+						Some(id) => {
+							manifest
+								.get(id)
+								.ok_or_else(|| Error::new(ErrorKind::VersionNotFound))?
+						},
+						None => minecraft::resolve_version(manifest, minecraft_version)?,
+					};
+					let minimum_version = manifest
+						.get(minimum)
+						.ok_or_else(|| Error::new(ErrorKind::VersionNotFound))?;
+
+					if version.release_time < minimum_version.release_time {
+						return Err(Error::with_source(
+							ErrorKind::IncompatibleVersion,
+							format!(
+								"{} requires minecraft {minimum} or newer, found {}",
+								component.id(),
+								version.id
+							),
+						));
+					}
+				},
+				Requirement::JavaAgent => {
+					if !java::supports_java_agent(java).await {
+						return Err(Error::with_source(
+							ErrorKind::JavaAgentUnsupported,
+							format!(
+								"{} needs a java runtime with java.instrument",
+								component.id()
+							),
+						));
+					}
+				},
+			}
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn single(value: &str) -> Argument {
+		Argument::Single(value.to_owned())
+	}
+
+	fn pair(name: &str, value: &str) -> Argument {
+		Argument::Pair(name.to_owned(), value.to_owned())
+	}
+
+	fn eq(name: &str, value: &str) -> Argument {
+		Argument::Eq(name.to_owned(), value.to_owned())
+	}
+
+	#[test]
+	fn parse_versions() {
+		let pinned: Version = "stable@2021-12-01".parse().unwrap();
+		assert_eq!(pinned, Version::Pinned {
+			tag: Tag::Stable,
+			before: "2021-12-01".to_owned(),
+		});
+		assert_eq!(pinned.to_string(), "stable@2021-12-01");
+		let json = serde_json::to_string(&pinned).unwrap();
+		assert_eq!(serde_json::from_str::<Version>(&json).unwrap(), pinned);
+
+		assert_eq!(
+			"latest-snapshot".parse::<Version>().unwrap(),
+			Version::Tag(Tag::LatestSnapshot)
+		);
+		// Only tags can be pinned
+		assert_eq!(
+			"1.20.1@2023".parse::<Version>().unwrap(),
+			Version::Id("1.20.1@2023".to_owned())
+		);
+		assert_eq!(
+			serde_json::from_str::<Version>("\"1.20.1\"").unwrap(),
+			Version::Id("1.20.1".to_owned())
+		);
+	}
+
+	#[test]
+	fn merge_replaces_single_valued() {
+		let mut target = vec![
+			pair("--version", "1.20.1"),
+			pair("--assetIndex", "5"),
+			single("-Xmx2G"),
+			eq("-Dlog4j.configurationFile", "vanilla.xml"),
+		];
+		merge_arguments(&mut target, [
+			pair("--version", "fabric-loader-0.14.21-1.20.1"),
+			single("-Xmx4G"),
+			eq("-Dlog4j.configurationFile", "fabric.xml"),
+		]);
+
+		assert_eq!(target, [
+			pair("--version", "fabric-loader-0.14.21-1.20.1"),
+			pair("--assetIndex", "5"),
+			single("-Xmx4G"),
+			eq("-Dlog4j.configurationFile", "fabric.xml"),
+		]);
+	}
+
+	#[test]
+	fn merge_appends_repeated_options() {
+		let mut target = vec![
+			pair("--add-opens", "java.base/java.util=ALL-UNNAMED"),
+			single("-XX:+UseG1GC"),
+		];
+		merge_arguments(&mut target, [
+			pair("--add-opens", "java.base/java.lang=ALL-UNNAMED"),
+			pair("--add-opens", "java.base/java.util=ALL-UNNAMED"),
+			single("-XX:+UseG1GC"),
+			single("--demo"),
+		]);
+
+		assert_eq!(target, [
+			pair("--add-opens", "java.base/java.util=ALL-UNNAMED"),
+			single("-XX:+UseG1GC"),
+			pair("--add-opens", "java.base/java.lang=ALL-UNNAMED"),
+			single("--demo"),
+		]);
+	}
 }
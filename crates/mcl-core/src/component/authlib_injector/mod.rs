@@ -1,7 +1,9 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use super::{Component, ComponentEnum, State, Version};
+use super::{Component, Layer, Requirement, State, Version};
+use crate::error::Error;
+use crate::Launcher;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct AuthlibInjector {
@@ -10,9 +12,18 @@ pub struct AuthlibInjector {
 
 #[async_trait(?Send)]
 impl Component for AuthlibInjector {
-	async fn install(&self, _result: &mut State) -> Result<(), super::Error> {
-		Ok(())
+	fn layer(&self) -> Layer {
+		Layer::Agent
+	}
+
+	fn requirements(&self) -> Vec<Requirement> {
+		vec![
+			Requirement::Component("minecraft-client"),
+			Requirement::JavaAgent,
+		]
 	}
 
-	fn is_compatible(&self, _component: &ComponentEnum) {}
+	async fn install(&self, _launcher: &Launcher, _result: &mut State) -> Result<(), Error> {
+		Ok(())
+	}
 }
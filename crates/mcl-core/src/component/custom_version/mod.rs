@@ -0,0 +1,214 @@
+//! Version JSONs shipped by OptiFine, LiteLoader, modpacks and custom clients,
+//! applied on top of the Mojang version they `inheritsFrom`.
+
+use std::path::{self, Path};
+
+use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
+use path_macro::path;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use super::minecraft::rules::{self, Rule};
+use super::minecraft::{arguments, library, version};
+use super::{Component, ComponentEnum, Layer, Requirement, State};
+use crate::error::{Error, ErrorKind, ResultExt};
+use crate::instance::Instance;
+use crate::lockfile::{FileKind, LockedComponent, LockedFile};
+use crate::maven::{self, Coordinate, Repository};
+use crate::Launcher;
+
+const MOJANG_LIBRARIES: &str = "https://libraries.minecraft.net/";
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Library {
+	Mojang(version::Library),
+	/// Older and third party JSONs only name a Maven artifact and optionally
+	/// the repository it lives in.
+	Maven {
+		name: String,
+		url: Option<String>,
+		rules: Option<Vec<Rule>>,
+	},
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Arguments {
+	#[serde(default)]
+	pub game: Vec<version::Argument>,
+	#[serde(default)]
+	pub jvm: Vec<version::Argument>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionJson {
+	pub id: String,
+	pub inherits_from: Option<String>,
+	pub main_class: Option<String>,
+	pub arguments: Option<Arguments>,
+	pub minecraft_arguments: Option<String>,
+	#[serde(default)]
+	pub libraries: Vec<Library>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct CustomVersion {
+	/// Path of the version JSON, relative to the instance directory.
+	pub file: String,
+}
+
+/// Copies a version JSON into the instance and adds it as a component.
+pub async fn import(
+	launcher: &Launcher,
+	instance: &mut Instance,
+	source: impl AsRef<Path>,
+) -> Result<(), Error> {
+	let bytes = fs::read(&source).await.with_path(&source)?;
+	let json: VersionJson = serde_json::from_slice(&bytes).with_path(&source)?;
+
+	// The id names the file, it must not lead out of the versions folder
+	let mut components = Path::new(&json.id).components();
+	if !matches!(
+		(components.next(), components.next()),
+		(Some(path::Component::Normal(_)), None)
+	) {
+		return Err(Error::with_source(
+			ErrorKind::Json,
+			format!("`{}` is not a valid version id", json.id),
+		))
+		.with_path(&source);
+	}
+
+	let file = format!("versions/{}.json", json.id);
+	let path = path!(instance.get_path(launcher) / file);
+	crate::utils::write(&path, bytes).await.with_path(&path)?;
+
+	instance
+		.add_component(
+			launcher,
+			ComponentEnum::CustomVersion(CustomVersion { file }),
+		)
+		.await
+}
+
+#[async_trait(?Send)]
+impl Component for CustomVersion {
+	fn layer(&self) -> Layer {
+		Layer::Override
+	}
+
+	fn requirements(&self) -> Vec<Requirement> {
+		vec![Requirement::Component("minecraft-client")]
+	}
+
+	async fn install(&self, launcher: &Launcher, result: &mut State) -> Result<(), Error> {
+		let game_version = result.minecraft_version.clone().ok_or_else(|| {
+			Error::with_source(
+				ErrorKind::MissingRequirement,
+				"custom-version requires minecraft-client",
+			)
+		})?;
+
+		let path = path!(result.instance_dir / self.file);
+		let bytes = fs::read(&path).await.with_path(&path)?;
+		let json: VersionJson = serde_json::from_slice(&bytes).with_path(&path)?;
+
+		if let Some(inherits_from) = &json.inherits_from {
+			if inherits_from != &game_version {
+				return Err(Error::with_source(
+					ErrorKind::IncompatibleVersion,
+					format!(
+						"{} inherits from {inherits_from} but the instance uses minecraft \
+						 {game_version}",
+						json.id
+					),
+				));
+			}
+		}
+
+		let game_version = &game_version;
+		let artifacts = futures::stream::iter(json.libraries.iter().map(|library| {
+			async move {
+				match library {
+					Library::Mojang(library) => {
+						library::download(launcher, game_version, library).await?;
+						Ok(None)
+					},
+					Library::Maven { rules, .. } if !rules.as_ref().is_none_or(rules::check) => {
+						Ok(None)
+					},
+					Library::Maven { name, url, .. } => {
+						let coordinate: Coordinate = name.parse()?;
+						let repositories: Vec<Repository> = std::iter::once(Repository::new(
+							url.as_deref().unwrap_or(MOJANG_LIBRARIES),
+						))
+						.chain(launcher.settings().maven_repositories.iter().cloned())
+						.collect();
+
+						maven::resolve(launcher, &repositories, &coordinate, None)
+							.await
+							.map(Some)
+					},
+				}
+			}
+		}))
+		.buffered(launcher.settings().library_concurrency)
+		.try_collect::<Vec<_>>()
+		.await?;
+
+		let mut locked_files = vec![LockedFile {
+			kind: FileKind::Version,
+			name: self.file.clone(),
+			sha1: openssl::sha::sha1(&bytes),
+		}];
+
+		for (library, artifact) in json.libraries.iter().zip(artifacts) {
+			match (library, artifact) {
+				(Library::Mojang(library), _) => {
+					library::apply(launcher, library, result, &mut locked_files)?;
+				},
+				(Library::Maven { .. }, Some(artifact)) => {
+					result.add_library(
+						artifact.coordinate.to_string(),
+						artifact.path.to_string_lossy(),
+					);
+					locked_files.push(LockedFile {
+						kind: FileKind::Library,
+						name: artifact.coordinate.to_string(),
+						sha1: artifact.sha1,
+					});
+				},
+				(Library::Maven { .. }, None) => {},
+			}
+		}
+
+		if let Some(main_class) = json.main_class {
+			result.main_class = main_class;
+		}
+
+		if let Some(json_arguments) = json.arguments {
+			result.add_jvm_arguments(arguments::convert(json_arguments.jvm));
+			result.add_game_arguments(arguments::convert(json_arguments.game));
+		} else if let Some(minecraft_arguments) = json.minecraft_arguments {
+			// Like the official launcher, a legacy argument string replaces the
+			// inherited one instead of adding to it
+			result.game_arguments.clear();
+			result.add_game_arguments(arguments::convert(
+				minecraft_arguments
+					.split_whitespace()
+					.map(|argument| version::Argument::Basic(argument.to_owned()))
+					.collect(),
+			));
+		}
+
+		result.lockfile.components.push(LockedComponent {
+			id: "custom-version".to_owned(),
+			version: json.id,
+			files: locked_files,
+		});
+
+		Ok(())
+	}
+}
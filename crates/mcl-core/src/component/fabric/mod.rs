@@ -1,40 +1,116 @@
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use super::Version;
+use super::loader::Meta;
+use super::{Component, Layer, Requirement, State, Version};
+use crate::error::Error;
+use crate::Launcher;
+
+const META: Meta = Meta {
+	id: "fabric-loader",
+	url: "https://meta.fabricmc.net/v2",
+	profile: "profile",
+};
+
+const SERVER_META: Meta = Meta {
+	id: "fabric-server",
+	url: "https://meta.fabricmc.net/v2",
+	profile: "server",
+};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct FabricLoader {
 	pub version: Version,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub resolved: Option<String>,
 }
 
 impl From<Version> for FabricLoader {
 	fn from(version: Version) -> Self {
-		Self { version }
+		Self {
+			version,
+			resolved: None,
+		}
+	}
+}
+
+#[async_trait(?Send)]
+impl Component for FabricLoader {
+	fn layer(&self) -> Layer {
+		Layer::Loader
+	}
+
+	fn requirements(&self) -> Vec<Requirement> {
+		vec![
+			Requirement::Component("minecraft-client"),
+			Requirement::MinecraftAtLeast("1.14"),
+			Requirement::Conflicts("quilt-loader"),
+		]
+	}
+
+	async fn resolve(
+		&mut self,
+		launcher: &Launcher,
+		update: bool,
+		_minecraft_version: Option<&str>,
+	) -> Result<bool, Error> {
+		META.resolve_component(launcher, &self.version, &mut self.resolved, update)
+			.await
+	}
+
+	async fn install(&self, launcher: &Launcher, result: &mut State) -> Result<(), Error> {
+		META.install_into(launcher, &self.version, self.resolved.as_deref(), result)
+			.await
 	}
 }
 
-// #[async_trait(?Send)]
-// impl Component for FabricLoaderComponent {
-//     async fn install(&self, _: &Instance) -> Result<InstallResult,
-// ComponentError> {         // let manifest =
-// self::version_manifest::get(&crate::HTTP).await?;         //
-//         // let partial_version = match &self.version {
-//         //     Version::Tag(Tag::Latest) => manifest
-//         //         .versions
-//         //         .first()
-//         //         .ok_or(ComponentError::VersionNotFound())?,
-//         //     Version::Tag(Tag::Stable) => manifest
-//         //         .versions
-//         //         .iter()
-//         //         .find(|version| version.type_ ==
-// self::version_manifest::VersionType::Release)         //
-// .ok_or(ComponentError::VersionNotFound())?,         //     Version::Id(id) =>
-// manifest         //         .versions
-//         //         .iter()
-//         //         .find(|version| &version.id == id)
-//         //         .ok_or(ComponentError::VersionNotFound())?,
-//         // };
-//     }
-//
-//     fn is_compatible(&self, _component: &AnyComponent) {}
-// }
+/// Fabric loader on a dedicated server, launched through Fabric's server
+/// profile.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct FabricServer {
+	pub version: Version,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub resolved: Option<String>,
+}
+
+impl From<Version> for FabricServer {
+	fn from(version: Version) -> Self {
+		Self {
+			version,
+			resolved: None,
+		}
+	}
+}
+
+#[async_trait(?Send)]
+impl Component for FabricServer {
+	fn layer(&self) -> Layer {
+		Layer::Loader
+	}
+
+	fn requirements(&self) -> Vec<Requirement> {
+		vec![
+			Requirement::Component("minecraft-server"),
+			Requirement::MinecraftAtLeast("1.14"),
+			Requirement::Conflicts("paper-server"),
+			Requirement::Conflicts("purpur-server"),
+		]
+	}
+
+	async fn resolve(
+		&mut self,
+		launcher: &Launcher,
+		update: bool,
+		_minecraft_version: Option<&str>,
+	) -> Result<bool, Error> {
+		SERVER_META
+			.resolve_component(launcher, &self.version, &mut self.resolved, update)
+			.await
+	}
+
+	async fn install(&self, launcher: &Launcher, result: &mut State) -> Result<(), Error> {
+		SERVER_META
+			.install_into(launcher, &self.version, self.resolved.as_deref(), result)
+			.await
+	}
+}
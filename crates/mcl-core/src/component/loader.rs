@@ -0,0 +1,252 @@
+//! Shared support for mod loaders published through a Fabric style meta API,
+//! which serves a launcher profile for every loader and game version.
+
+use futures::{StreamExt, TryStreamExt};
+use hex::FromHex;
+use path_macro::path;
+use serde::{Deserialize, Serialize};
+
+use super::minecraft::{arguments, version};
+use super::{State, Tag, Version};
+use crate::error::{Error, ErrorKind};
+use crate::lockfile::{FileKind, LockedComponent, LockedFile};
+use crate::maven::{self, Coordinate, Repository};
+use crate::utils::get_json;
+use crate::Launcher;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoaderVersion {
+	pub version: String,
+	pub stable: Option<bool>,
+}
+
+impl LoaderVersion {
+	/// Quilt does not mark stable builds, but unstable ones carry a pre-release
+	/// suffix like `-beta.1`.
+	#[must_use]
+	pub fn is_stable(&self) -> bool {
+		self.stable.unwrap_or(!self.version.contains('-'))
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProfileArguments {
+	#[serde(default)]
+	pub game: Vec<version::Argument>,
+	#[serde(default)]
+	pub jvm: Vec<version::Argument>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProfileLibrary {
+	pub name: String,
+	pub url: String,
+	pub sha1: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+	pub id: String,
+	pub inherits_from: String,
+	pub main_class: String,
+	pub arguments: Option<ProfileArguments>,
+	pub libraries: Vec<ProfileLibrary>,
+}
+
+pub struct Meta {
+	/// Id of the component using this meta server.
+	pub id: &'static str,
+	pub url: &'static str,
+	/// Launch profile to install, `profile` for clients or `server`.
+	pub profile: &'static str,
+}
+
+impl Meta {
+	/// Loader versions, newest first.
+	pub async fn versions(&self, launcher: &Launcher) -> Result<Vec<LoaderVersion>, Error> {
+		get_json(
+			launcher.http(),
+			launcher
+				.settings()
+				.mirror(&format!("{}/versions/loader", self.url)),
+			path!(launcher.data_dir() / "meta" / format!("{}.json", self.id)),
+			None,
+			Some(launcher.settings().manifest_ttl),
+		)
+		.await
+	}
+
+	/// Resolves `version` into `resolved`, unless that is set already and the
+	/// component is not being updated. Returns whether `resolved` changed.
+	pub async fn resolve_component(
+		&self,
+		launcher: &Launcher,
+		version: &Version,
+		resolved: &mut Option<String>,
+		update: bool,
+	) -> Result<bool, Error> {
+		if resolved.is_some() && !update {
+			return Ok(false);
+		}
+
+		let version = self.resolve(launcher, version).await?;
+		let changed = resolved.as_ref() != Some(&version);
+		*resolved = Some(version);
+
+		Ok(changed)
+	}
+
+	/// Installs the `resolved` loader version, or the one `version` resolves
+	/// to if the component was never resolved.
+	pub async fn install_into(
+		&self,
+		launcher: &Launcher,
+		version: &Version,
+		resolved: Option<&str>,
+		result: &mut State,
+	) -> Result<(), Error> {
+		let loader_version = match resolved {
+			Some(loader_version) => loader_version.to_owned(),
+			None => self.resolve(launcher, version).await?,
+		};
+
+		self.install(launcher, &loader_version, result).await
+	}
+
+	async fn resolve(&self, launcher: &Launcher, version: &Version) -> Result<String, Error> {
+		let versions = match version {
+			Version::Id(id) => return Ok(id.clone()),
+			Version::Pinned { .. } => {
+				return Err(Error::with_source(
+					ErrorKind::VersionNotFound,
+					format!("{} versions can not be pinned to a date", self.id),
+				));
+			},
+			Version::Tag(_) => self.versions(launcher).await?,
+		};
+
+		versions
+			.into_iter()
+			.find(|loader| !matches!(version, Version::Tag(Tag::Stable)) || loader.is_stable())
+			.map(|loader| loader.version)
+			.ok_or_else(|| Error::new(ErrorKind::VersionNotFound))
+	}
+
+	async fn install(
+		&self,
+		launcher: &Launcher,
+		loader_version: &str,
+		result: &mut State,
+	) -> Result<(), Error> {
+		let data_dir = launcher.data_dir();
+		let game_version = result.minecraft_version.clone().ok_or_else(|| {
+			Error::with_source(
+				ErrorKind::MissingRequirement,
+				format!("{} requires minecraft", self.id),
+			)
+		})?;
+
+		let profile: Profile = get_json(
+			launcher.http(),
+			launcher.settings().mirror(&format!(
+				"{}/versions/loader/{game_version}/{loader_version}/{}/json",
+				self.url, self.profile
+			)),
+			path!(data_dir / "meta" / self.id / game_version / format!("{loader_version}.json")),
+			None,
+			Some(launcher.settings().manifest_ttl),
+		)
+		.await?;
+
+		let artifacts = futures::stream::iter(profile.libraries.iter().map(|library| {
+			async move {
+				let coordinate: Coordinate = library.name.parse()?;
+				let sha1 = library
+					.sha1
+					.as_ref()
+					.and_then(|sha1| <[u8; 20]>::from_hex(sha1).ok());
+				let repositories: Vec<Repository> = std::iter::once(Repository::new(&library.url))
+					.chain(launcher.settings().maven_repositories.iter().cloned())
+					.collect();
+
+				maven::resolve(launcher, &repositories, &coordinate, sha1).await
+			}
+		}))
+		.buffered(launcher.settings().library_concurrency)
+		.try_collect::<Vec<_>>()
+		.await?;
+
+		let mut locked_files = Vec::new();
+		for artifact in artifacts {
+			result.add_library(
+				artifact.coordinate.to_string(),
+				artifact.path.to_string_lossy(),
+			);
+			locked_files.push(LockedFile {
+				kind: FileKind::Library,
+				name: artifact.coordinate.to_string(),
+				sha1: artifact.sha1,
+			});
+		}
+
+		result.main_class = profile.main_class;
+		if let Some(profile_arguments) = profile.arguments {
+			result.add_jvm_arguments(arguments::convert(profile_arguments.jvm));
+			result.add_game_arguments(arguments::convert(profile_arguments.game));
+		}
+
+		result.lockfile.components.push(LockedComponent {
+			id: self.id.to_owned(),
+			version: loader_version.to_owned(),
+			files: locked_files,
+		});
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const META: Meta = Meta {
+		id: "test-loader",
+		url: "http://127.0.0.1:9/v2",
+		profile: "profile",
+	};
+
+	#[tokio::test]
+	async fn resolve_component() {
+		let data_dir = std::env::temp_dir().join(format!("mcl-loader-{}", std::process::id()));
+		let launcher = Launcher::new(&data_dir);
+
+		let mut resolved = None;
+		let version = Version::Id("0.15.0".to_owned());
+		assert!(META
+			.resolve_component(&launcher, &version, &mut resolved, false)
+			.await
+			.unwrap());
+		assert_eq!(resolved.as_deref(), Some("0.15.0"));
+		assert!(!META
+			.resolve_component(&launcher, &version, &mut resolved, true)
+			.await
+			.unwrap());
+
+		// Resolved versions are kept until updated
+		let version = Version::Id("0.16.0".to_owned());
+		assert!(!META
+			.resolve_component(&launcher, &version, &mut resolved, false)
+			.await
+			.unwrap());
+		assert_eq!(resolved.as_deref(), Some("0.15.0"));
+
+		let pinned: Version = "stable@2021-12-01".parse().unwrap();
+		let err = META
+			.resolve_component(&launcher, &pinned, &mut resolved, true)
+			.await
+			.unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::VersionNotFound);
+		assert_eq!(resolved.as_deref(), Some("0.15.0"));
+	}
+}
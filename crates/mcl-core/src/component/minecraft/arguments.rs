@@ -34,11 +34,11 @@ pub fn convert(arguments: Vec<version::Argument>) -> Vec<Argument> {
 				if let Some((argument, value)) = argument.split_once('=') {
 					out.push(Argument::Eq(argument.to_owned(), value.to_owned()));
 					continue;
-				} else if let Some(next_argument) = iter.peek() {
-					if let Some('$') = next_argument.chars().next() {
-						out.push(Argument::Pair(argument, iter.next().unwrap()));
-						continue;
-					}
+				} else if let Some(value) =
+					iter.next_if(|next| next.starts_with('$') || !next.starts_with('-'))
+				{
+					out.push(Argument::Pair(argument, value));
+					continue;
 				}
 				out.push(Argument::Single(argument));
 			}
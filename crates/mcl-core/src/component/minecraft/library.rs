@@ -5,16 +5,35 @@ use zip::read::ZipArchive;
 
 use super::rules;
 use super::version::Library;
-use crate::utils::{self, Hash, RequestError};
-use crate::DIRS;
+use crate::component::State;
+use crate::error::{Context, Error, ErrorKind, ResultExt};
+use crate::lockfile::{FileKind, LockedFile};
+use crate::utils::{self, Hash};
+use crate::Launcher;
+
+/// The classifier of natives for the current OS.
+pub fn natives_classifier() -> Result<&'static str, Error> {
+	match env::consts::OS {
+		"linux" => Ok("natives-linux"),
+		"macos" => Ok("natives-osx"),
+		"windows" => Ok("natives-windows"),
+		_ => Err(Error::new(ErrorKind::UnsupportedPlatform)),
+	}
+}
+
+pub async fn download(
+	launcher: &Launcher,
+	version_id: &String,
+	library: &Library,
+) -> Result<(), Error> {
+	let data_dir = launcher.data_dir();
 
-pub async fn download(version_id: &String, library: &Library) -> Result<(), RequestError> {
 	match library {
 		Library::ExtractNatives {
+			name,
 			downloads,
 			rules,
 			extract,
-			..
 		} => {
 			if let Some(rules) = rules {
 				if !rules::check(rules) {
@@ -22,42 +41,35 @@ pub async fn download(version_id: &String, library: &Library) -> Result<(), Requ
 				}
 			};
 
-			let artifact = match env::consts::OS {
-				"linux" => {
-					downloads
-						.classifiers
-						.get("natives-linux")
-						.expect("this shouldn't ever happen")
-				},
-				"macos" => {
-					downloads
-						.classifiers
-						.get("natives-osx")
-						.expect("this shouldn't ever happen")
-				},
-				"windows" => {
-					downloads
-						.classifiers
-						.get("natives-windows")
-						.expect("this shouldn't ever happen")
-				},
-				_ => todo!(),
-			};
+			let classifier = natives_classifier()?;
+
+			let artifact = downloads.classifiers.get(classifier).ok_or_else(|| {
+				Error::new(ErrorKind::MissingNatives)
+					.context_with(Context::Library(format!("{name}:{classifier}")))
+			})?;
 
-			let artifact_path = path!(DIRS.data_dir() / "libraries" / artifact.path);
+			let artifact_path = path!(data_dir / "libraries" / artifact.path);
 
 			utils::download_file(
-				&artifact.url,
+				launcher.http(),
+				launcher.settings().mirror(&artifact.url),
 				&artifact_path,
 				Some(Hash::Sha1(artifact.sha1)),
 				false,
 			)
 			.await?;
 
-			let mut jar = ZipArchive::new(std::fs::File::open(artifact_path)?).unwrap();
+			let mut jar =
+				ZipArchive::new(std::fs::File::open(&artifact_path).with_path(&artifact_path)?)
+					.with_path(&artifact_path)?;
 
 			for i in 0..jar.len() {
-				let mut file = jar.by_index(i).unwrap();
+				let mut file = jar.by_index(i).with_path(&artifact_path)?;
+
+				let enclosed_name = match file.enclosed_name() {
+					Some(enclosed_name) => enclosed_name.to_owned(),
+					None => continue,
+				};
 
 				if file.is_file()
 					&& !match extract {
@@ -70,18 +82,13 @@ pub async fn download(version_id: &String, library: &Library) -> Result<(), Requ
 						None => file.name().starts_with("META-INF/"),
 					} {
 					let mut buf: Vec<u8> = Vec::new();
-					io::copy(&mut file, &mut buf).unwrap();
-
-					utils::write(
-						path!(
-							DIRS.data_dir()
-								/ "versions" / version_id / "natives"
-								/ file.enclosed_name().unwrap()
-						),
-						buf,
-					)
-					.await
-					.unwrap();
+					io::copy(&mut file, &mut buf).with_path(&artifact_path)?;
+
+					let native_path =
+						path!(data_dir / "versions" / version_id / "natives" / enclosed_name);
+					utils::write(&native_path, buf)
+						.await
+						.with_path(&native_path)?;
 				};
 			}
 		},
@@ -89,10 +96,11 @@ pub async fn download(version_id: &String, library: &Library) -> Result<(), Requ
 			downloads, rules, ..
 		} => {
 			utils::download_file(
-				downloads.artifact.url.clone(),
-				path!(DIRS.data_dir() / "libraries" / downloads.artifact.path),
+				launcher.http(),
+				launcher.settings().mirror(&downloads.artifact.url),
+				path!(data_dir / "libraries" / downloads.artifact.path),
 				Some(Hash::Sha1(downloads.artifact.sha1)),
-				rules.is_some() && !rules::check(rules.as_ref().unwrap()),
+				matches!(rules, Some(rules) if !rules::check(rules)),
 			)
 			.await?;
 		},
@@ -100,3 +108,52 @@ pub async fn download(version_id: &String, library: &Library) -> Result<(), Requ
 
 	Ok(())
 }
+
+/// Puts an already downloaded library on the classpath if its rules allow it,
+/// recording it in `locked_files`.
+pub fn apply(
+	launcher: &Launcher,
+	library: &Library,
+	result: &mut State,
+	locked_files: &mut Vec<LockedFile>,
+) -> Result<(), Error> {
+	match library {
+		Library::SingleArtifact {
+			name,
+			downloads,
+			rules,
+		} => {
+			if rules.as_ref().is_none_or(rules::check) {
+				result.add_library(
+					name.clone(),
+					path!(launcher.data_dir() / "libraries" / downloads.artifact.path)
+						.to_string_lossy(),
+				);
+				locked_files.push(LockedFile {
+					kind: FileKind::Library,
+					name: name.clone(),
+					sha1: downloads.artifact.sha1,
+				});
+			}
+		},
+		Library::ExtractNatives {
+			name,
+			downloads,
+			rules,
+			..
+		} => {
+			if rules.as_ref().is_none_or(rules::check) {
+				let classifier = natives_classifier()?;
+				if let Some(artifact) = downloads.classifiers.get(classifier) {
+					locked_files.push(LockedFile {
+						kind: FileKind::Library,
+						name: format!("{name}:{classifier}"),
+						sha1: artifact.sha1,
+					});
+				}
+			}
+		},
+	}
+
+	Ok(())
+}
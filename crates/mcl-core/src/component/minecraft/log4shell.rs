@@ -0,0 +1,120 @@
+//! Mitigations for CVE-2021-44228 in the log4j bundled with Minecraft 1.7 to
+//! 1.18.0, as recommended by Mojang.
+
+use hex::FromHex;
+use path_macro::path;
+use serde::Serialize;
+
+use super::version::Library;
+use crate::component::{Argument, LoggingConfig, State};
+use crate::error::{Error, ErrorKind};
+use crate::lockfile::{FileKind, LockedFile};
+use crate::maven::Coordinate;
+use crate::utils::{download_file, Hash};
+use crate::Launcher;
+
+/// Disables message lookups in log4j 2.10 and newer.
+pub const NO_LOOKUPS_ARGUMENT: &str = "-Dlog4j2.formatMsgNoLookups=true";
+
+struct PatchedConfig {
+	id: &'static str,
+	url: &'static str,
+	sha1: &'static str,
+}
+
+/// For log4j 2.0-beta9, used by 1.7 to 1.11.2.
+const CONFIG_17_111: PatchedConfig = PatchedConfig {
+	id: "log4j2_17-111.xml",
+	url: "https://launcher.mojang.com/v1/objects/4bb89a97a66f350bc9f73b3ca8509632682aea2e/log4j2_17-111.xml",
+	sha1: "4bb89a97a66f350bc9f73b3ca8509632682aea2e",
+};
+
+/// For log4j 2.1 to 2.9, used by 1.12 to 1.16.5.
+const CONFIG_112_116: PatchedConfig = PatchedConfig {
+	id: "log4j2_112-116.xml",
+	url: "https://launcher.mojang.com/v1/objects/02937d122c86ce73319ef9975b58896fc1b491d1/log4j2_112-116.xml",
+	sha1: "02937d122c86ce73319ef9975b58896fc1b491d1",
+};
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Mitigation {
+	/// The log config was replaced with one that disables lookups.
+	PatchedConfig { log4j: String, config: String },
+	/// Lookups were disabled with `-Dlog4j2.formatMsgNoLookups=true`, which
+	/// log4j supports since 2.10.
+	FormatMsgNoLookups { log4j: String },
+}
+
+/// `major.minor` of a log4j version like `2.0-beta9` or `2.14.1`.
+fn major_minor(version: &str) -> Option<(u32, u32)> {
+	let mut parts = version.split(['.', '-']);
+	let major = parts.next()?.parse().ok()?;
+	let minor = parts.next()?.parse().ok()?;
+	Some((major, minor))
+}
+
+/// Version of the vulnerable log4j-core in `libraries`, if there is one.
+fn vulnerable_log4j(libraries: &[Library]) -> Option<String> {
+	libraries.iter().find_map(|library| {
+		let (Library::SingleArtifact { name, .. } | Library::ExtractNatives { name, .. }) = library;
+		let coordinate: Coordinate = name.parse().ok()?;
+
+		(coordinate.group == "org.apache.logging.log4j"
+			&& coordinate.artifact == "log4j-core"
+			&& major_minor(&coordinate.version)
+				.is_some_and(|(major, minor)| major == 2 && minor < 15))
+		.then_some(coordinate.version)
+	})
+}
+
+/// Patches the log config or JVM arguments in `result` if `libraries` contain
+/// a vulnerable log4j-core, returning what was done.
+pub async fn apply(
+	launcher: &Launcher,
+	libraries: &[Library],
+	result: &mut State,
+	locked_files: &mut Vec<LockedFile>,
+) -> Result<Option<Mitigation>, Error> {
+	let Some(log4j) = vulnerable_log4j(libraries) else {
+		return Ok(None);
+	};
+
+	let config = match major_minor(&log4j) {
+		Some((_, 0)) => CONFIG_17_111,
+		Some((_, minor)) if minor < 10 => CONFIG_112_116,
+		_ => {
+			result.add_jvm_arguments([Argument::Single(NO_LOOKUPS_ARGUMENT.to_owned())]);
+			return Ok(Some(Mitigation::FormatMsgNoLookups { log4j }));
+		},
+	};
+
+	let sha1 = <[u8; 20]>::from_hex(config.sha1)
+		.map_err(|err| Error::with_source(ErrorKind::HashMismatch, err))?;
+	let path = path!(launcher.data_dir() / "assets" / "log_configs" / config.id);
+
+	download_file(
+		launcher.http(),
+		launcher.settings().mirror(config.url),
+		&path,
+		Some(Hash::Sha1(sha1)),
+		false,
+	)
+	.await?;
+
+	locked_files.push(LockedFile {
+		kind: FileKind::LogConfig,
+		name: config.id.to_owned(),
+		sha1,
+	});
+	result.logging = Some(LoggingConfig {
+		argument: "-Dlog4j.configurationFile=${path}".to_owned(),
+		path,
+		type_: "log4j2-xml".to_owned(),
+	});
+
+	Ok(Some(Mitigation::PatchedConfig {
+		log4j,
+		config: config.id.to_owned(),
+	}))
+}
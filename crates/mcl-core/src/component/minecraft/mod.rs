@@ -1,162 +1,382 @@
-use std::time::Duration;
-
 use async_trait::async_trait;
-use futures::StreamExt;
+use futures::TryStreamExt;
 use path_macro::path;
 use serde::{Deserialize, Serialize};
 
-use super::{Component, ComponentEnum, State, Tag, Version};
+use super::{Component, Layer, LoggingConfig, State, Tag, Version};
+use crate::error::{Error, ErrorKind};
+use crate::lockfile::{FileKind, LockedComponent, LockedFile};
 use crate::utils::{self, download_file, get_json, Hash};
-use crate::DIRS;
+use crate::Launcher;
 
 pub mod arguments;
 pub mod asset_index;
 pub mod library;
+pub mod log4shell;
 pub mod rules;
 pub mod version;
 pub mod version_manifest;
 
+use version_manifest::{VersionFilter, VersionType};
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct MinecraftClient {
 	pub version: Version,
+	/// Concrete version id `version` resolved to, used until the instance is
+	/// updated.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub resolved: Option<String>,
 }
 
 impl From<Version> for MinecraftClient {
 	fn from(version: Version) -> Self {
-		Self { version }
+		Self {
+			version,
+			resolved: None,
+		}
 	}
 }
 
+/// Fetches Mojang's version manifest, reusing the cached copy for
+/// [`Settings::manifest_ttl`](crate::Settings::manifest_ttl).
+pub async fn get_manifest(launcher: &Launcher) -> Result<version_manifest::VersionManifest, Error> {
+	utils::get_json(
+		launcher.http(),
+		launcher
+			.settings()
+			.mirror("https://piston-meta.mojang.com/mc/game/version_manifest_v2.json"),
+		path!(launcher.data_dir() / "meta" / "minecraft.json"),
+		None,
+		Some(launcher.settings().manifest_ttl),
+	)
+	.await
+}
+
+/// Finds the manifest entry `version` refers to, using the manifest's `latest`
+/// field for tags.
+pub fn resolve_version<'a>(
+	manifest: &'a version_manifest::VersionManifest,
+	version: &Version,
+) -> Result<&'a version_manifest::Version, Error> {
+	let resolved = match version {
+		Version::Tag(Tag::Stable) => manifest.latest_release(),
+		Version::Tag(Tag::LatestSnapshot) => manifest.latest_snapshot(),
+		Version::Tag(Tag::Latest) => {
+			match (manifest.latest_release(), manifest.latest_snapshot()) {
+				(Some(release), Some(snapshot)) => {
+					Some(
+						if release.release_time >= snapshot.release_time {
+							release
+						} else {
+							snapshot
+						},
+					)
+				},
+				(release, snapshot) => release.or(snapshot),
+			}
+		},
+		Version::Pinned { tag, before } => {
+			let filter = VersionFilter {
+				types: match tag {
+					Tag::Latest => Vec::new(),
+					Tag::Stable => vec![VersionType::Release],
+					Tag::LatestSnapshot => vec![VersionType::Snapshot],
+				},
+				released_before: Some(before.clone()),
+				..VersionFilter::default()
+			};
+			manifest
+				.filter(&filter)
+				.into_iter()
+				.max_by(|a, b| a.release_time.cmp(&b.release_time))
+		},
+		Version::Id(id) => manifest.get(id),
+	};
+
+	resolved.ok_or_else(|| Error::new(ErrorKind::VersionNotFound))
+}
+
+/// Fetches the version JSON of `resolved`, or of what `version` resolves to if
+/// it is not pinned yet, along with the SHA-1 of the JSON.
+pub async fn get_version(
+	launcher: &Launcher,
+	version: &Version,
+	resolved: Option<&str>,
+) -> Result<(version::Version, [u8; 20]), Error> {
+	let manifest = get_manifest(launcher).await?;
+
+	let partial_version = match resolved {
+		Some(id) => manifest.get(id).ok_or(ErrorKind::VersionNotFound)?,
+		None => resolve_version(&manifest, version)?,
+	};
+
+	let version = get_json(
+		launcher.http(),
+		launcher.settings().mirror(&partial_version.url),
+		path!(launcher.data_dir() / "versions" / partial_version.id / "meta.json"),
+		Some(Hash::Sha1(partial_version.sha1)),
+		None,
+	)
+	.await?;
+
+	Ok((version, partial_version.sha1))
+}
+
 #[async_trait(?Send)]
 impl Component for MinecraftClient {
-	async fn install(&self, result: &mut State) -> Result<(), super::Error> {
-		let manifest = utils::get_json::<version_manifest::VersionManifest>(
-			"https://piston-meta.mojang.com/mc/game/version_manifest_v2.json",
-			path!(DIRS.data_dir() / "meta" / "minecraft.json"),
-			None,
-			Some(Duration::from_secs(30 * 60)),
-		)
-		.await?;
+	fn layer(&self) -> Layer {
+		Layer::BaseGame
+	}
 
-		let partial_version = match &self.version {
-			Version::Tag(Tag::Latest) => {
-				manifest
-					.versions
-					.first()
-					.ok_or(super::Error::VersionNotFound())?
-			},
-			Version::Tag(Tag::Stable) => {
-				manifest
-					.versions
-					.iter()
-					.find(|version| version.type_ == version_manifest::VersionType::Release)
-					.ok_or(super::Error::VersionNotFound())?
-			},
-			Version::Id(id) => {
-				manifest
-					.versions
-					.iter()
-					.find(|version| &version.id == id)
-					.ok_or(super::Error::VersionNotFound())?
-			},
-		};
+	async fn resolve(
+		&mut self,
+		launcher: &Launcher,
+		update: bool,
+		_minecraft_version: Option<&str>,
+	) -> Result<bool, Error> {
+		if self.resolved.is_some() && !update {
+			return Ok(false);
+		}
 
-		let version: version::Version = get_json(
-			&partial_version.url,
-			path!(DIRS.data_dir() / "versions" / partial_version.id / "meta.json"),
-			Some(Hash::Sha1(partial_version.sha1)),
-			None,
-		)
-		.await?;
+		let manifest = get_manifest(launcher).await?;
+		let id = resolve_version(&manifest, &self.version)?.id.clone();
+		let changed = self.resolved.as_ref() != Some(&id);
+		self.resolved = Some(id);
+
+		Ok(changed)
+	}
+
+	async fn install(&self, launcher: &Launcher, result: &mut State) -> Result<(), Error> {
+		let data_dir = launcher.data_dir();
+		let settings = launcher.settings();
 
-		let client_jar = path!(DIRS.data_dir() / "versions" / version.id / "client.jar");
+		let (version, version_sha1) =
+			get_version(launcher, &self.version, self.resolved.as_deref()).await?;
+
+		let client_jar = path!(data_dir / "versions" / version.id / "client.jar");
 
 		download_file(
-			version.downloads.client.url,
+			launcher.http(),
+			settings.mirror(&version.downloads.client.url),
 			&client_jar,
 			Some(Hash::Sha1(version.downloads.client.sha1)),
 			false,
 		)
 		.await?;
 
-		result.classpath.push(client_jar.to_string_lossy().into());
+		result.add_library(
+			format!("com.mojang:minecraft:{}:client", version.id),
+			client_jar.to_string_lossy(),
+		);
 
-		let tasks = futures::stream::iter(
+		futures::stream::iter(
 			version
 				.libraries
 				.iter()
-				.map(|library| library::download(&version.id, library)),
-		);
+				.map(|library| Ok(library::download(launcher, &version.id, library))),
+		)
+		.try_for_each_concurrent(settings.library_concurrency, |task| task)
+		.await?;
 
-		tasks
-			.for_each_concurrent(5, |task| {
-				async {
-					println!("{:?}", task.await);
-				}
-			})
-			.await;
-
-		for library in version.libraries {
-			if let version::Library::SingleArtifact {
-				downloads, rules, ..
-			} = library
-			{
-				if rules.is_none() || rules::check(&rules.unwrap()) {
-					result.classpath.push(
-						path!(DIRS.data_dir() / "libraries" / downloads.artifact.path)
-							.to_string_lossy()
-							.into(),
-					);
-				}
-			}
+		let mut locked_files = vec![
+			LockedFile {
+				kind: FileKind::Version,
+				name: format!("{}.json", version.id),
+				sha1: version_sha1,
+			},
+			LockedFile {
+				kind: FileKind::Client,
+				name: "client.jar".to_owned(),
+				sha1: version.downloads.client.sha1,
+			},
+			LockedFile {
+				kind: FileKind::AssetIndex,
+				name: format!("{}.json", version.asset_index.id),
+				sha1: version.asset_index.sha1,
+			},
+		];
+
+		for library in &version.libraries {
+			library::apply(launcher, library, result, &mut locked_files)?;
+		}
+
+		if let Some(logging) = &version.logging {
+			let file = &logging.client.file;
+			let path = path!(data_dir / "assets" / "log_configs" / file.id);
+
+			download_file(
+				launcher.http(),
+				settings.mirror(&file.url),
+				&path,
+				Some(Hash::Sha1(file.sha1)),
+				false,
+			)
+			.await?;
+
+			locked_files.push(LockedFile {
+				kind: FileKind::LogConfig,
+				name: file.id.clone(),
+				sha1: file.sha1,
+			});
+			result.logging = Some(LoggingConfig {
+				argument: logging.client.argument.clone(),
+				path,
+				type_: logging.client.type_.clone(),
+			});
 		}
 
+		result.log4shell_mitigation =
+			log4shell::apply(launcher, &version.libraries, result, &mut locked_files).await?;
+
+		result.lockfile.components.push(LockedComponent {
+			id: "minecraft-client".to_owned(),
+			version: version.id.clone(),
+			files: locked_files,
+		});
+
 		let asset_index: asset_index::AssetIndex = get_json(
-			version.asset_index.url,
-			path!(
-				DIRS.data_dir() / "assets" / "indexes" / format!("{}.json", version.asset_index.id)
-			),
+			launcher.http(),
+			settings.mirror(&version.asset_index.url),
+			path!(data_dir / "assets" / "indexes" / format!("{}.json", version.asset_index.id)),
 			Some(Hash::Sha1(version.asset_index.sha1)),
 			None,
 		)
 		.await?;
 
-		let tasks = futures::stream::iter(asset_index.objects.iter().map(|(_, asset)| {
-			let hash_str = hex::encode(&asset.hash);
+		futures::stream::iter(asset_index.objects.values().map(|asset| {
+			let hash_str = hex::encode(asset.hash);
 			let hash_prefix = hash_str.chars().take(2).collect::<String>();
 
-			let url = format!(
+			let url = settings.mirror(&format!(
 				"https://resources.download.minecraft.net/{}/{}",
 				hash_prefix, hash_str
-			);
+			));
 
-			download_file(
+			Ok(download_file(
+				launcher.http(),
 				url,
-				path!(DIRS.data_dir() / "assets" / "objects" / hash_prefix / hash_str),
+				path!(data_dir / "assets" / "objects" / hash_prefix / hash_str),
 				Some(Hash::Sha1(asset.hash)),
 				false,
-			)
-		}));
-
-		tasks
-			.for_each_concurrent(30, |task| {
-				async {
-					println!("{:?}", task.await);
-				}
-			})
-			.await;
+			))
+		}))
+		.try_for_each_concurrent(settings.asset_concurrency, |task| {
+			async {
+				task.await?;
+				Ok::<_, Error>(())
+			}
+		})
+		.await?;
 
 		if let Some(arguments) = version.arguments {
-			result
-				.jvm_arguments
-				.extend(arguments::convert(arguments.jvm));
-			result
-				.game_arguments
-				.extend(arguments::convert(arguments.game));
+			result.add_jvm_arguments(arguments::convert(arguments.jvm));
+			result.add_game_arguments(arguments::convert(arguments.game));
+		} else if let Some(minecraft_arguments) = version.minecraft_arguments {
+			// Versions before 1.13 only list game arguments as a single string
+			result.add_jvm_arguments(arguments::convert(vec![
+				version::Argument::Basic("-Djava.library.path=${natives_directory}".to_owned()),
+				version::Argument::Basic("-cp".to_owned()),
+				version::Argument::Basic("${classpath}".to_owned()),
+			]));
+			result.add_game_arguments(arguments::convert(
+				minecraft_arguments
+					.split_whitespace()
+					.map(|argument| version::Argument::Basic(argument.to_owned()))
+					.collect(),
+			));
 		}
 
+		let assets_root = path!(data_dir / "assets").to_string_lossy().into_owned();
+		result.main_class = version.main_class;
+		result.minecraft_version = Some(version.id.clone());
+		result.variables.extend([
+			("version_name".to_owned(), version.id.clone()),
+			("version_type".to_owned(), version.type_.as_str().to_owned()),
+			("assets_root".to_owned(), assets_root.clone()),
+			("game_assets".to_owned(), assets_root),
+			("assets_index_name".to_owned(), version.asset_index.id),
+			(
+				"natives_directory".to_owned(),
+				path!(data_dir / "versions" / version.id / "natives")
+					.to_string_lossy()
+					.into_owned(),
+			),
+		]);
+
 		Ok(())
 	}
+}
+
+#[cfg(test)]
+mod tests {
+	use version_manifest::{Release, VersionManifest};
+
+	use super::*;
+
+	fn manifest() -> VersionManifest {
+		let version = |id: &str, type_, release_time: &str| {
+			version_manifest::Version {
+				id: id.to_owned(),
+				type_,
+				url: format!("https://example.com/{id}.json"),
+				time: release_time.to_owned(),
+				release_time: release_time.to_owned(),
+				sha1: [0; 20],
+				compliance_level: 1,
+			}
+		};
+
+		VersionManifest {
+			latest: Release {
+				release: "1.20.1".to_owned(),
+				snapshot: "23w31a".to_owned(),
+			},
+			versions: vec![
+				version("23w31a", VersionType::Snapshot, "2023-08-02T10:00:00+00:00"),
+				version("1.20.1", VersionType::Release, "2023-06-12T13:25:51+00:00"),
+				version("1.18", VersionType::Release, "2021-11-30T09:16:29+00:00"),
+				version("21w44a", VersionType::Snapshot, "2021-11-03T14:38:07+00:00"),
+				version("1.17.1", VersionType::Release, "2021-07-06T12:01:34+00:00"),
+			],
+		}
+	}
 
-	fn is_compatible(&self, _component: &ComponentEnum) {}
+	fn resolve(manifest: &VersionManifest, version: &str) -> Result<String, Error> {
+		resolve_version(manifest, &version.parse().unwrap()).map(|version| version.id.clone())
+	}
+
+	#[test]
+	fn resolve_tags() {
+		let mut manifest = manifest();
+		assert_eq!(resolve(&manifest, "stable").unwrap(), "1.20.1");
+		assert_eq!(resolve(&manifest, "latest-snapshot").unwrap(), "23w31a");
+		assert_eq!(resolve(&manifest, "latest").unwrap(), "23w31a");
+		assert_eq!(resolve(&manifest, "1.17.1").unwrap(), "1.17.1");
+
+		// The release is newer once a snapshot cycle ends
+		manifest.latest.snapshot = "21w44a".to_owned();
+		assert_eq!(resolve(&manifest, "latest").unwrap(), "1.20.1");
+
+		let err = resolve(&manifest, "1.99").unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::VersionNotFound);
+	}
+
+	#[test]
+	fn resolve_pinned() {
+		let manifest = manifest();
+		assert_eq!(resolve(&manifest, "latest@2021-12-01").unwrap(), "1.18");
+		assert_eq!(resolve(&manifest, "stable@2021-11-15").unwrap(), "1.17.1");
+		assert_eq!(
+			resolve(&manifest, "latest-snapshot@2021-12").unwrap(),
+			"21w44a"
+		);
+		// `before` is exclusive
+		assert_eq!(
+			resolve(&manifest, "stable@2021-11-30T09:16:29+00:00").unwrap(),
+			"1.17.1"
+		);
+
+		let err = resolve(&manifest, "stable@2020").unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::VersionNotFound);
+	}
 }
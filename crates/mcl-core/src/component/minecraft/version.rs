@@ -13,6 +13,18 @@ pub enum VersionType {
 	OldAlpha,
 }
 
+impl VersionType {
+	#[must_use]
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			VersionType::Snapshot => "snapshot",
+			VersionType::Release => "release",
+			VersionType::OldBeta => "old_beta",
+			VersionType::OldAlpha => "old_alpha",
+		}
+	}
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct AssetIndex {
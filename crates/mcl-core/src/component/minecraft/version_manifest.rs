@@ -1,18 +1,20 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+use crate::utils::glob_match;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VersionManifest {
 	pub latest: Release,
 	pub versions: Vec<Version>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Release {
 	pub release: String,
 	pub snapshot: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum VersionType {
 	Snapshot,
@@ -21,7 +23,7 @@ pub enum VersionType {
 	OldAlpha,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Version {
 	pub id: String,
@@ -33,3 +35,63 @@ pub struct Version {
 	pub sha1: [u8; 20],
 	pub compliance_level: u8,
 }
+
+/// Criteria for [`VersionManifest::filter`]. Empty criteria match everything.
+///
+/// Dates are compared against `releaseTime` as strings, so any prefix of an
+/// RFC 3339 timestamp works, e.g. `2021` or `2021-12-10`.
+#[derive(Debug, Clone, Default)]
+pub struct VersionFilter {
+	pub types: Vec<VersionType>,
+	/// Inclusive lower bound on the release time.
+	pub released_after: Option<String>,
+	/// Exclusive upper bound on the release time.
+	pub released_before: Option<String>,
+	/// Glob matched against the version id, `*` and `?` are supported.
+	pub id: Option<String>,
+}
+
+impl VersionFilter {
+	#[must_use]
+	pub fn matches(&self, version: &Version) -> bool {
+		(self.types.is_empty() || self.types.contains(&version.type_))
+			&& self
+				.released_after
+				.as_ref()
+				.is_none_or(|after| version.release_time.as_str() >= after.as_str())
+			&& self
+				.released_before
+				.as_ref()
+				.is_none_or(|before| version.release_time.as_str() < before.as_str())
+			&& self
+				.id
+				.as_ref()
+				.is_none_or(|pattern| glob_match(pattern, &version.id))
+	}
+}
+
+impl VersionManifest {
+	#[must_use]
+	pub fn get(&self, id: &str) -> Option<&Version> {
+		self.versions.iter().find(|version| version.id == id)
+	}
+
+	#[must_use]
+	pub fn latest_release(&self) -> Option<&Version> {
+		self.get(&self.latest.release)
+	}
+
+	#[must_use]
+	pub fn latest_snapshot(&self) -> Option<&Version> {
+		self.get(&self.latest.snapshot)
+	}
+
+	/// Versions matching `filter`, newest first.
+	#[must_use]
+	pub fn filter(&self, filter: &VersionFilter) -> Vec<&Version> {
+		self.versions
+			.iter()
+			.filter(|version| filter.matches(version))
+			.collect()
+	}
+}
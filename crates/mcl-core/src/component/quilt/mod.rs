@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::loader::Meta;
+use super::{Component, Layer, Requirement, State, Version};
+use crate::error::Error;
+use crate::Launcher;
+
+const META: Meta = Meta {
+	id: "quilt-loader",
+	url: "https://meta.quiltmc.org/v3",
+	profile: "profile",
+};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct QuiltLoader {
+	pub version: Version,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub resolved: Option<String>,
+}
+
+impl From<Version> for QuiltLoader {
+	fn from(version: Version) -> Self {
+		Self {
+			version,
+			resolved: None,
+		}
+	}
+}
+
+#[async_trait(?Send)]
+impl Component for QuiltLoader {
+	fn layer(&self) -> Layer {
+		Layer::Loader
+	}
+
+	fn requirements(&self) -> Vec<Requirement> {
+		vec![
+			Requirement::Component("minecraft-client"),
+			Requirement::MinecraftAtLeast("1.14"),
+			Requirement::Conflicts("fabric-loader"),
+		]
+	}
+
+	async fn resolve(
+		&mut self,
+		launcher: &Launcher,
+		update: bool,
+		_minecraft_version: Option<&str>,
+	) -> Result<bool, Error> {
+		META.resolve_component(launcher, &self.version, &mut self.resolved, update)
+			.await
+	}
+
+	async fn install(&self, launcher: &Launcher, result: &mut State) -> Result<(), Error> {
+		META.install_into(launcher, &self.version, self.resolved.as_deref(), result)
+			.await
+	}
+}
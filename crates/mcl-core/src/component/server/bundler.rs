@@ -0,0 +1,211 @@
+//! Server jars since 1.18 are bundlers that carry the actual server jar and
+//! its libraries in `META-INF` and unpack them on start. The launcher unpacks
+//! them itself so the server can be started with a plain classpath.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use hex::FromHex;
+use path_macro::path;
+use tokio::fs;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::error::{Error, ErrorKind, ResultExt};
+use crate::{utils, Launcher};
+
+pub struct BundledFile {
+	/// Maven style name.
+	pub name: String,
+	pub path: PathBuf,
+	pub sha1: [u8; 20],
+}
+
+pub struct Bundle {
+	pub main_class: String,
+	/// The server jar followed by its libraries.
+	pub files: Vec<BundledFile>,
+}
+
+/// A line of `META-INF/versions.list` or `META-INF/libraries.list`.
+#[derive(Debug, PartialEq, Eq)]
+struct Listed<'a> {
+	sha256: [u8; 32],
+	id: &'a str,
+	/// Relative to the directory the list is unpacked into.
+	file: &'a Path,
+}
+
+/// Parses a file list, where every line is `<sha256>\t<id>\t<path>`. Lines with
+/// fewer fields are skipped, paths that would leave the target directory are
+/// an error.
+fn parse_list(listing: &str) -> Result<Vec<Listed<'_>>, Error> {
+	let mut files = Vec::new();
+
+	for line in listing.lines() {
+		let mut parts = line.split('\t');
+		let (Some(sha256), Some(id), Some(file)) = (parts.next(), parts.next(), parts.next())
+		else {
+			continue;
+		};
+		let sha256 = <[u8; 32]>::from_hex(sha256)
+			.map_err(|err| Error::with_source(ErrorKind::HashMismatch, err))?;
+		if !utils::is_contained(Path::new(file)) {
+			return Err(Error::with_source(
+				ErrorKind::Zip,
+				format!("`{file}` is outside the directory it is unpacked into"),
+			));
+		}
+
+		files.push(Listed {
+			sha256,
+			id,
+			file: Path::new(file),
+		});
+	}
+
+	Ok(files)
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<Vec<u8>>, Error> {
+	match archive.by_name(name) {
+		Ok(mut file) => {
+			let mut bytes = Vec::new();
+			file.read_to_end(&mut bytes)?;
+			Ok(Some(bytes))
+		},
+		Err(ZipError::FileNotFound) => Ok(None),
+		Err(err) => Err(err.into()),
+	}
+}
+
+/// `Main-Class` from the manifest of a jar.
+pub fn main_class(jar: &Path) -> Result<Option<String>, Error> {
+	let mut archive = ZipArchive::new(File::open(jar).with_path(jar)?).with_path(jar)?;
+	let Some(manifest) = read_entry(&mut archive, "META-INF/MANIFEST.MF").with_path(jar)? else {
+		return Ok(None);
+	};
+
+	Ok(String::from_utf8_lossy(&manifest).lines().find_map(|line| {
+		line.strip_prefix("Main-Class:")
+			.map(|main_class| main_class.trim().to_owned())
+	}))
+}
+
+/// Unpacks a bundler jar into the data directory, skipping files that are
+/// already there. Returns `None` for older server jars, which are not
+/// bundlers.
+pub async fn extract(
+	launcher: &Launcher,
+	jar: &Path,
+	version_id: &str,
+) -> Result<Option<Bundle>, Error> {
+	let mut archive = ZipArchive::new(File::open(jar).with_path(jar)?).with_path(jar)?;
+	let Some(main_class) = read_entry(&mut archive, "META-INF/main-class").with_path(jar)? else {
+		return Ok(None);
+	};
+
+	let data_dir = launcher.data_dir();
+	let mut files = Vec::new();
+
+	for (list, target_dir) in [
+		(
+			"versions",
+			path!(data_dir / "versions" / version_id / "server"),
+		),
+		("libraries", path!(data_dir / "libraries")),
+	] {
+		let Some(listing) =
+			read_entry(&mut archive, &format!("META-INF/{list}.list")).with_path(jar)?
+		else {
+			continue;
+		};
+
+		let listing = String::from_utf8_lossy(&listing);
+		for Listed { sha256, id, file } in parse_list(&listing).with_path(jar)? {
+			let path = path!(target_dir / file);
+			let bytes = match fs::read(&path).await {
+				Ok(bytes) if openssl::sha::sha256(&bytes) == sha256 => bytes,
+				_ => {
+					let entry = format!("META-INF/{list}/{}", file.display());
+					let bytes = read_entry(&mut archive, &entry)
+						.with_path(jar)?
+						.ok_or_else(|| {
+							Error::with_source(ErrorKind::Zip, format!("missing {entry}"))
+						})
+						.with_path(jar)?;
+					if openssl::sha::sha256(&bytes) != sha256 {
+						return Err(Error::new(ErrorKind::HashMismatch)).with_path(&path);
+					}
+
+					utils::write(&path, &bytes).await.with_path(&path)?;
+					bytes
+				},
+			};
+
+			files.push(BundledFile {
+				name: match list {
+					"versions" => format!("com.mojang:minecraft:{id}:server"),
+					_ => id.to_owned(),
+				},
+				path,
+				sha1: openssl::sha::sha1(&bytes),
+			});
+		}
+	}
+
+	Ok(Some(Bundle {
+		main_class: String::from_utf8_lossy(&main_class).trim().to_owned(),
+		files,
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_lists() {
+		let sha256 = "aa".repeat(32);
+		let library = "com/mojang/brigadier/1.1.8/brigadier-1.1.8.jar";
+		let listing = [
+			&format!("{sha256}\tcom.mojang:brigadier:1.1.8\t{library}\r"),
+			"",
+			"incomplete\tline",
+			&format!("{sha256}\t1.20.1\tserver-1.20.1.jar"),
+		]
+		.join("\n");
+
+		assert_eq!(parse_list(&listing).unwrap(), [
+			Listed {
+				sha256: [0xaa; 32],
+				id: "com.mojang:brigadier:1.1.8",
+				file: Path::new(library),
+			},
+			Listed {
+				sha256: [0xaa; 32],
+				id: "1.20.1",
+				file: Path::new("server-1.20.1.jar"),
+			},
+		]);
+	}
+
+	#[test]
+	fn reject_invalid_lists() {
+		let sha256 = "aa".repeat(32);
+		let err = parse_list("abc\tid\tfile.jar").unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::HashMismatch);
+
+		for file in [
+			"../evil.jar",
+			"a/../../evil.jar",
+			"/etc/passwd",
+			"./a.jar",
+			"",
+		] {
+			let err = parse_list(&format!("{sha256}\tid\t{file}")).unwrap_err();
+			assert_eq!(err.kind(), ErrorKind::Zip, "{file}");
+		}
+	}
+}
@@ -0,0 +1,218 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use path_macro::path;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use super::minecraft::{get_manifest, get_version, log4shell, resolve_version};
+use super::{Argument, Component, Layer, Requirement, State, Version};
+use crate::error::{Error, ErrorKind, ResultExt};
+use crate::lockfile::{FileKind, LockedComponent, LockedFile};
+use crate::utils::{self, download_file, Hash};
+use crate::Launcher;
+
+pub mod bundler;
+pub mod paper;
+pub mod properties;
+
+pub use paper::{PaperServer, PurpurServer};
+pub use properties::ServerProperties;
+
+/// The vanilla dedicated server, launched headless.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct MinecraftServer {
+	pub version: Version,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub resolved: Option<String>,
+}
+
+impl From<Version> for MinecraftServer {
+	fn from(version: Version) -> Self {
+		Self {
+			version,
+			resolved: None,
+		}
+	}
+}
+
+/// Whether the EULA has been accepted in `eula.txt` of the instance.
+pub async fn eula_accepted(instance_dir: impl AsRef<Path>) -> bool {
+	fs::read_to_string(path!(instance_dir.as_ref() / "eula.txt"))
+		.await
+		.is_ok_and(|eula| eula.lines().any(|line| line.trim() == "eula=true"))
+}
+
+/// Records that the user agreed to the Minecraft EULA, which the server
+/// requires before it starts.
+pub async fn accept_eula(instance_dir: impl AsRef<Path>) -> Result<(), Error> {
+	let path = path!(instance_dir.as_ref() / "eula.txt");
+	utils::write(
+		&path,
+		"# By changing the setting below to TRUE you are indicating your agreement to our EULA \
+		 (https://aka.ms/MinecraftEULA).\neula=true\n",
+	)
+	.await
+	.with_path(&path)
+}
+
+#[must_use]
+pub fn properties_path(instance_dir: impl AsRef<Path>) -> PathBuf {
+	path!(instance_dir.as_ref() / "server.properties")
+}
+
+/// Downloads a server jar that brings its own libraries, like the Paper and
+/// Purpur launchers, and makes it the whole classpath.
+async fn install_server_jar(
+	launcher: &Launcher,
+	id: &str,
+	name: String,
+	url: String,
+	path: PathBuf,
+	hash: Hash,
+	result: &mut State,
+) -> Result<(), Error> {
+	download_file(
+		launcher.http(),
+		launcher.settings().mirror(&url),
+		&path,
+		Some(hash),
+		false,
+	)
+	.await?;
+
+	let sha1 = openssl::sha::sha1(&fs::read(&path).await.with_path(&path)?);
+	let main_class = bundler::main_class(&path)?
+		.ok_or_else(|| Error::with_source(ErrorKind::Zip, "server jar has no main class"))
+		.with_path(&path)?;
+
+	// The jar brings its own log4j and config, the mitigations for the vanilla
+	// server's log4j do not apply to it
+	result.libraries.clear();
+	result.logging = None;
+	if result.log4shell_mitigation.take().is_some() {
+		result.jvm_arguments.retain(|argument| {
+			*argument != Argument::Single(log4shell::NO_LOOKUPS_ARGUMENT.to_owned())
+		});
+	}
+	result.add_library(name.clone(), path.to_string_lossy());
+	result.main_class = main_class;
+
+	let version = name.rsplit(':').next().unwrap_or_default().to_owned();
+	result.lockfile.components.push(LockedComponent {
+		id: id.to_owned(),
+		version,
+		files: vec![LockedFile {
+			kind: FileKind::Server,
+			name,
+			sha1,
+		}],
+	});
+
+	Ok(())
+}
+
+#[async_trait(?Send)]
+impl Component for MinecraftServer {
+	fn layer(&self) -> Layer {
+		Layer::BaseGame
+	}
+
+	fn requirements(&self) -> Vec<Requirement> {
+		vec![Requirement::Conflicts("minecraft-client")]
+	}
+
+	async fn resolve(
+		&mut self,
+		launcher: &Launcher,
+		update: bool,
+		_minecraft_version: Option<&str>,
+	) -> Result<bool, Error> {
+		if self.resolved.is_some() && !update {
+			return Ok(false);
+		}
+
+		let manifest = get_manifest(launcher).await?;
+		let id = resolve_version(&manifest, &self.version)?.id.clone();
+		let changed = self.resolved.as_ref() != Some(&id);
+		self.resolved = Some(id);
+
+		Ok(changed)
+	}
+
+	async fn install(&self, launcher: &Launcher, result: &mut State) -> Result<(), Error> {
+		let (version, version_sha1) =
+			get_version(launcher, &self.version, self.resolved.as_deref()).await?;
+
+		let server = version.downloads.server.as_ref().ok_or_else(|| {
+			Error::with_source(
+				ErrorKind::VersionNotFound,
+				format!("{} has no dedicated server", version.id),
+			)
+		})?;
+		let server_jar = path!(launcher.data_dir() / "versions" / version.id / "server.jar");
+
+		download_file(
+			launcher.http(),
+			launcher.settings().mirror(&server.url),
+			&server_jar,
+			Some(Hash::Sha1(server.sha1)),
+			false,
+		)
+		.await?;
+
+		let mut locked_files = vec![
+			LockedFile {
+				kind: FileKind::Version,
+				name: format!("{}.json", version.id),
+				sha1: version_sha1,
+			},
+			LockedFile {
+				kind: FileKind::Server,
+				name: "server.jar".to_owned(),
+				sha1: server.sha1,
+			},
+		];
+
+		match bundler::extract(launcher, &server_jar, &version.id).await? {
+			Some(bundle) => {
+				for file in bundle.files {
+					result.add_library(file.name.clone(), file.path.to_string_lossy());
+					locked_files.push(LockedFile {
+						kind: FileKind::Library,
+						name: file.name,
+						sha1: file.sha1,
+					});
+				}
+				result.main_class = bundle.main_class;
+			},
+			None => {
+				result.add_library(
+					format!("com.mojang:minecraft:{}:server", version.id),
+					server_jar.to_string_lossy(),
+				);
+				result.main_class = bundler::main_class(&server_jar)?
+					.unwrap_or_else(|| "net.minecraft.server.MinecraftServer".to_owned());
+			},
+		}
+
+		// The server bundles the same log4j as the client of its version
+		result.log4shell_mitigation =
+			log4shell::apply(launcher, &version.libraries, result, &mut locked_files).await?;
+
+		result.lockfile.components.push(LockedComponent {
+			id: "minecraft-server".to_owned(),
+			version: version.id.clone(),
+			files: locked_files,
+		});
+
+		result.add_jvm_arguments([Argument::Pair("-cp".to_owned(), "${classpath}".to_owned())]);
+		result.add_game_arguments([Argument::Single("nogui".to_owned())]);
+		result.minecraft_version = Some(version.id.clone());
+		result
+			.variables
+			.insert("version_name".to_owned(), version.id);
+
+		Ok(())
+	}
+}
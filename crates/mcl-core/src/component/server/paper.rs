@@ -0,0 +1,315 @@
+//! Paper and Purpur, resolved through their download APIs.
+
+use async_trait::async_trait;
+use hex::FromHex;
+use path_macro::path;
+use serde::{Deserialize, Serialize};
+
+use super::install_server_jar;
+use crate::component::{Component, Layer, Requirement, State, Tag, Version};
+use crate::error::{Error, ErrorKind};
+use crate::utils::{get_json, Hash};
+use crate::Launcher;
+
+fn game_version<'a>(id: &str, minecraft_version: Option<&'a str>) -> Result<&'a str, Error> {
+	minecraft_version.ok_or_else(|| {
+		Error::with_source(
+			ErrorKind::MissingRequirement,
+			format!("{id} requires minecraft-server"),
+		)
+	})
+}
+
+fn pinned_error(id: &str) -> Error {
+	Error::with_source(
+		ErrorKind::VersionNotFound,
+		format!("{id} builds can not be pinned to a date"),
+	)
+}
+
+#[derive(Deserialize, Debug)]
+struct PaperDownload {
+	name: String,
+	sha256: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct PaperDownloads {
+	application: PaperDownload,
+}
+
+#[derive(Deserialize, Debug)]
+struct PaperBuild {
+	build: u32,
+	channel: String,
+	downloads: PaperDownloads,
+}
+
+#[derive(Deserialize, Debug)]
+struct PaperBuilds {
+	builds: Vec<PaperBuild>,
+}
+
+async fn paper_builds(launcher: &Launcher, game_version: &str) -> Result<PaperBuilds, Error> {
+	get_json(
+		launcher.http(),
+		launcher.settings().mirror(&format!(
+			"{}/projects/paper/versions/{game_version}/builds",
+			launcher.settings().paper_api
+		)),
+		path!(launcher.data_dir() / "meta" / "paper-server" / format!("{game_version}.json")),
+		None,
+		Some(launcher.settings().manifest_ttl),
+	)
+	.await
+}
+
+/// The build `version` refers to, builds are listed oldest first.
+fn select_paper_build<'a>(
+	builds: &'a PaperBuilds,
+	version: &Version,
+) -> Result<&'a PaperBuild, Error> {
+	match version {
+		Version::Tag(Tag::Stable) => {
+			builds
+				.builds
+				.iter()
+				.rev()
+				.find(|build| build.channel == "default")
+		},
+		Version::Tag(_) => builds.builds.last(),
+		Version::Id(id) => {
+			builds
+				.builds
+				.iter()
+				.find(|build| build.build.to_string() == *id)
+		},
+		Version::Pinned { .. } => return Err(pinned_error("paper-server")),
+	}
+	.ok_or_else(|| Error::new(ErrorKind::VersionNotFound))
+}
+
+/// The Paper server. `version` is a build number, or `latest` or `stable` for
+/// the newest (stable) build for the Minecraft version of the instance.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct PaperServer {
+	pub version: Version,
+	/// Build number `version` resolved to.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub resolved: Option<String>,
+}
+
+impl From<Version> for PaperServer {
+	fn from(version: Version) -> Self {
+		Self {
+			version,
+			resolved: None,
+		}
+	}
+}
+
+#[async_trait(?Send)]
+impl Component for PaperServer {
+	fn layer(&self) -> Layer {
+		Layer::Loader
+	}
+
+	fn requirements(&self) -> Vec<Requirement> {
+		vec![
+			Requirement::Component("minecraft-server"),
+			Requirement::Conflicts("purpur-server"),
+			Requirement::Conflicts("fabric-server"),
+		]
+	}
+
+	async fn resolve(
+		&mut self,
+		launcher: &Launcher,
+		update: bool,
+		minecraft_version: Option<&str>,
+	) -> Result<bool, Error> {
+		if self.resolved.is_some() && !update {
+			return Ok(false);
+		}
+
+		let game_version = game_version("paper-server", minecraft_version)?;
+		let builds = paper_builds(launcher, game_version).await?;
+		let build = select_paper_build(&builds, &self.version)?
+			.build
+			.to_string();
+		let changed = self.resolved.as_ref() != Some(&build);
+		self.resolved = Some(build);
+
+		Ok(changed)
+	}
+
+	async fn install(&self, launcher: &Launcher, result: &mut State) -> Result<(), Error> {
+		let game_version = game_version("paper-server", result.minecraft_version.as_deref())?;
+		let api = &launcher.settings().paper_api;
+
+		let builds = paper_builds(launcher, game_version).await?;
+		let version = match &self.resolved {
+			Some(build) => Version::Id(build.clone()),
+			None => self.version.clone(),
+		};
+		let build = select_paper_build(&builds, &version)?;
+
+		let download = &build.downloads.application;
+		let sha256 = <[u8; 32]>::from_hex(&download.sha256)
+			.map_err(|err| Error::with_source(ErrorKind::HashMismatch, err))?;
+
+		install_server_jar(
+			launcher,
+			"paper-server",
+			format!("io.papermc:paper:{game_version}-{}", build.build),
+			format!(
+				"{api}/projects/paper/versions/{game_version}/builds/{}/downloads/{}",
+				build.build, download.name
+			),
+			path!(launcher.data_dir() / "versions" / game_version / download.name),
+			Hash::Sha256(sha256),
+			result,
+		)
+		.await
+	}
+}
+
+#[derive(Deserialize, Debug)]
+struct PurpurBuildList {
+	latest: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct PurpurVersion {
+	builds: PurpurBuildList,
+}
+
+#[derive(Deserialize, Debug)]
+struct PurpurBuild {
+	build: String,
+	/// The API publishes no stronger checksum.
+	md5: String,
+}
+
+/// The build number `version` refers to.
+async fn purpur_build(
+	launcher: &Launcher,
+	game_version: &str,
+	version: &Version,
+) -> Result<String, Error> {
+	match version {
+		Version::Tag(_) => {
+			let version: PurpurVersion = get_json(
+				launcher.http(),
+				launcher.settings().mirror(&format!(
+					"{}/purpur/{game_version}",
+					launcher.settings().purpur_api
+				)),
+				path!(
+					launcher.data_dir() / "meta" / "purpur-server" / game_version / "builds.json"
+				),
+				None,
+				Some(launcher.settings().manifest_ttl),
+			)
+			.await?;
+			Ok(version.builds.latest)
+		},
+		Version::Id(id) => Ok(id.clone()),
+		Version::Pinned { .. } => Err(pinned_error("purpur-server")),
+	}
+}
+
+/// The Purpur server. `version` is a build number or a tag for the newest
+/// build for the Minecraft version of the instance. The Purpur API only
+/// publishes MD5 checksums, so unlike Paper builds the jars are verified with
+/// MD5 alone.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct PurpurServer {
+	pub version: Version,
+	/// Build number `version` resolved to.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub resolved: Option<String>,
+}
+
+impl From<Version> for PurpurServer {
+	fn from(version: Version) -> Self {
+		Self {
+			version,
+			resolved: None,
+		}
+	}
+}
+
+#[async_trait(?Send)]
+impl Component for PurpurServer {
+	fn layer(&self) -> Layer {
+		Layer::Loader
+	}
+
+	fn requirements(&self) -> Vec<Requirement> {
+		vec![
+			Requirement::Component("minecraft-server"),
+			Requirement::Conflicts("paper-server"),
+			Requirement::Conflicts("fabric-server"),
+		]
+	}
+
+	async fn resolve(
+		&mut self,
+		launcher: &Launcher,
+		update: bool,
+		minecraft_version: Option<&str>,
+	) -> Result<bool, Error> {
+		if self.resolved.is_some() && !update {
+			return Ok(false);
+		}
+
+		let game_version = game_version("purpur-server", minecraft_version)?;
+		let build = purpur_build(launcher, game_version, &self.version).await?;
+		let changed = self.resolved.as_ref() != Some(&build);
+		self.resolved = Some(build);
+
+		Ok(changed)
+	}
+
+	async fn install(&self, launcher: &Launcher, result: &mut State) -> Result<(), Error> {
+		let game_version = game_version("purpur-server", result.minecraft_version.as_deref())?;
+		let api = &launcher.settings().purpur_api;
+
+		let build = match &self.resolved {
+			Some(build) => build.clone(),
+			None => purpur_build(launcher, game_version, &self.version).await?,
+		};
+		let build: PurpurBuild = get_json(
+			launcher.http(),
+			launcher
+				.settings()
+				.mirror(&format!("{api}/purpur/{game_version}/{build}")),
+			path!(
+				launcher.data_dir()
+					/ "meta" / "purpur-server"
+					/ game_version / format!("{build}.json")
+			),
+			None,
+			Some(launcher.settings().manifest_ttl),
+		)
+		.await?;
+		let md5 = <[u8; 16]>::from_hex(&build.md5)
+			.map_err(|err| Error::with_source(ErrorKind::HashMismatch, err))?;
+
+		install_server_jar(
+			launcher,
+			"purpur-server",
+			format!("org.purpurmc:purpur:{game_version}-{}", build.build),
+			format!("{api}/purpur/{game_version}/{}/download", build.build),
+			path!(
+				launcher.data_dir()
+					/ "versions" / game_version
+					/ format!("purpur-{game_version}-{}.jar", build.build)
+			),
+			Hash::Md5(md5),
+			result,
+		)
+		.await
+	}
+}
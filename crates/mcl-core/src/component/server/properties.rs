@@ -0,0 +1,239 @@
+//! `server.properties` in the Java properties format, edited line by line so
+//! comments and the order of keys survive.
+
+use std::path::Path;
+
+use tokio::fs;
+
+use crate::error::{Error, ResultExt};
+use crate::utils;
+
+#[derive(Debug, Clone, Default)]
+pub struct ServerProperties {
+	lines: Vec<String>,
+}
+
+/// Splits a line into its unescaped key and value, `None` for comments and
+/// blank lines.
+fn parse_line(line: &str) -> Option<(String, String)> {
+	let line = line.trim_start();
+	if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+		return None;
+	}
+
+	let mut escaped = false;
+	let separator = line.char_indices().find_map(|(i, c)| {
+		match (escaped, c) {
+			(false, '\\') => escaped = true,
+			(false, '=' | ':' | ' ' | '\t') => return Some(i),
+			_ => escaped = false,
+		}
+		None
+	});
+
+	Some(match separator {
+		Some(i) => {
+			let value = line[i..].trim_start();
+			let value = value
+				.strip_prefix(['=', ':'])
+				.map_or(value, str::trim_start);
+			(unescape(&line[..i]), unescape(value))
+		},
+		None => (unescape(line), String::new()),
+	})
+}
+
+fn unescape(text: &str) -> String {
+	let mut result = String::with_capacity(text.len());
+	let mut chars = text.chars();
+	// `\uXXXX` escapes in a row, decoded together as characters outside the
+	// BMP take two
+	let mut units = Vec::new();
+	let flush = |result: &mut String, units: &mut Vec<u16>| {
+		result.extend(char::decode_utf16(units.drain(..)).map(|c| c.unwrap_or('\u{fffd}')));
+	};
+
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			flush(&mut result, &mut units);
+			result.push(c);
+			continue;
+		}
+
+		let escaped = chars.next();
+		if escaped == Some('u') {
+			let code: String = chars.by_ref().take(4).collect();
+			units.push(u16::from_str_radix(&code, 16).unwrap_or(0xfffd));
+			continue;
+		}
+
+		flush(&mut result, &mut units);
+		match escaped {
+			Some('t') => result.push('\t'),
+			Some('n') => result.push('\n'),
+			Some('r') => result.push('\r'),
+			Some('f') => result.push('\u{c}'),
+			Some(c) => result.push(c),
+			None => {},
+		}
+	}
+	flush(&mut result, &mut units);
+
+	result
+}
+
+/// Escapes like `Properties.store`, writing non-ASCII characters as `\uXXXX`
+/// so older servers that read the file as Latin-1 understand it.
+fn escape(text: &str, key: bool) -> String {
+	let mut result = String::with_capacity(text.len());
+
+	for (i, c) in text.chars().enumerate() {
+		match c {
+			'\\' => result.push_str("\\\\"),
+			'\t' => result.push_str("\\t"),
+			'\n' => result.push_str("\\n"),
+			'\r' => result.push_str("\\r"),
+			'\u{c}' => result.push_str("\\f"),
+			'=' | ':' | '#' | '!' => {
+				result.push('\\');
+				result.push(c);
+			},
+			' ' if key || i == 0 => result.push_str("\\ "),
+			c if c.is_ascii() => result.push(c),
+			c => {
+				for unit in c.encode_utf16(&mut [0; 2]) {
+					result.push_str(&format!("\\u{unit:04X}"));
+				}
+			},
+		}
+	}
+
+	result
+}
+
+impl ServerProperties {
+	/// Reads `path`, a missing file has no properties.
+	pub async fn load(path: impl AsRef<Path>) -> Result<ServerProperties, Error> {
+		match fs::read_to_string(&path).await {
+			Ok(text) => {
+				Ok(ServerProperties {
+					lines: text.lines().map(ToOwned::to_owned).collect(),
+				})
+			},
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+			Err(err) => Err(err).with_path(path),
+		}
+	}
+
+	pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+		let mut text = self.lines.join("\n");
+		text.push('\n');
+		utils::write(&path, text).await.with_path(path)
+	}
+
+	#[must_use]
+	pub fn get(&self, key: &str) -> Option<String> {
+		self.iter().find(|(k, _)| k == key).map(|(_, value)| value)
+	}
+
+	/// Changes the value of `key` in place, or appends it.
+	pub fn set(&mut self, key: &str, value: &str) {
+		let line = format!("{}={}", escape(key, true), escape(value, false));
+
+		match self
+			.lines
+			.iter_mut()
+			.find(|line| parse_line(line).is_some_and(|(k, _)| k == key))
+		{
+			Some(existing) => *existing = line,
+			None => self.lines.push(line),
+		}
+	}
+
+	/// Removes `key`, returning whether it was set.
+	pub fn remove(&mut self, key: &str) -> bool {
+		let len = self.lines.len();
+		self.lines
+			.retain(|line| parse_line(line).is_none_or(|(k, _)| k != key));
+		self.lines.len() != len
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (String, String)> + '_ {
+		self.lines.iter().filter_map(|line| parse_line(line))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn escape_round_trip() {
+		for text in [
+			"plain",
+			" leading space",
+			"a=b:c#d!e",
+			"back\\slash",
+			"tab\tnew\nline\rfeed\u{c}",
+			"§6Minecraft Server ✓ 🎮",
+		] {
+			let mut properties = ServerProperties::default();
+			properties.set(text, text);
+			assert_eq!(properties.get(text).as_deref(), Some(text), "{text}");
+			assert!(properties.lines[0].is_ascii());
+		}
+	}
+
+	#[test]
+	fn parse_separators() {
+		let properties = ServerProperties {
+			lines: [
+				"# comment",
+				"! also a comment",
+				"",
+				"motd=A Minecraft Server",
+				"level-name : world",
+				"  gamemode survival",
+				"level-seed=",
+				"white-list",
+				"key\\ with\\ spaces=value",
+				"motd2=\\u00a7aGreen",
+			]
+			.map(ToOwned::to_owned)
+			.to_vec(),
+		};
+
+		assert_eq!(
+			properties.iter().collect::<Vec<_>>(),
+			[
+				("motd", "A Minecraft Server"),
+				("level-name", "world"),
+				("gamemode", "survival"),
+				("level-seed", ""),
+				("white-list", ""),
+				("key with spaces", "value"),
+				("motd2", "§aGreen"),
+			]
+			.map(|(key, value)| (key.to_owned(), value.to_owned()))
+		);
+	}
+
+	#[test]
+	fn set_in_place() {
+		let mut properties = ServerProperties {
+			lines: ["# comment", "motd=old", "pvp=true"]
+				.map(ToOwned::to_owned)
+				.to_vec(),
+		};
+		properties.set("motd", "new");
+		properties.set("difficulty", "hard");
+		assert!(properties.remove("pvp"));
+		assert!(!properties.remove("pvp"));
+
+		assert_eq!(properties.lines, [
+			"# comment",
+			"motd=new",
+			"difficulty=hard"
+		]);
+	}
+}
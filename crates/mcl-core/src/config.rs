@@ -0,0 +1,315 @@
+//! The launcher wide configuration file, `config.json` in the platform config
+//! directory.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use directories::ProjectDirs;
+use path_macro::path;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::fs;
+
+use crate::error::{Error, ErrorKind, ResultExt};
+use crate::http::HttpSettings;
+use crate::launch::LaunchSettings;
+use crate::launcher::{Mirror, Settings};
+use crate::maven::Repository;
+use crate::utils;
+
+/// Version of the config format written by this launcher.
+pub const CONFIG_VERSION: u64 = 2;
+
+/// Upgrades a config in place by one version.
+type Migration = fn(&mut Value) -> Result<(), Error>;
+
+/// Upgrades a config from the version at its index to the next one.
+const MIGRATIONS: &[Migration] = &[
+	// Configs written before the format was versioned have no `version` field
+	// but otherwise match version 1
+	|_| Ok(()),
+	// The proxy moved into the HTTP settings
+	|config| {
+		let config = config
+			.as_object_mut()
+			.ok_or_else(|| invalid("expected an object"))?;
+		if let Some(proxy) = config.remove("proxy") {
+			config
+				.entry("http")
+				.or_insert_with(|| Value::Object(Default::default()))
+				.as_object_mut()
+				.ok_or_else(|| invalid("http: expected an object"))?
+				.insert("proxy".to_owned(), proxy);
+		}
+		Ok(())
+	},
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Concurrency {
+	/// How many libraries are downloaded at once.
+	pub libraries: usize,
+	/// How many asset objects are downloaded at once.
+	pub assets: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub version: u64,
+	/// Where instances, libraries and assets are stored, the platform data
+	/// directory if unset.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub data_dir: Option<PathBuf>,
+	/// Defaults every instance inherits.
+	pub launch: LaunchSettings,
+	pub mirrors: Vec<Mirror>,
+	pub maven_repositories: Vec<String>,
+	/// Base URL of the PaperMC download API.
+	pub paper_api: String,
+	/// Base URL of the Purpur download API.
+	pub purpur_api: String,
+	/// Base URL of the Modrinth API.
+	pub modrinth_api: String,
+	pub concurrency: Concurrency,
+	/// Seconds a cached version manifest is used before refetching it.
+	pub manifest_ttl: u64,
+	/// How many launch logs are kept per instance.
+	pub kept_logs: usize,
+	pub http: HttpSettings,
+	/// Username of the account used when none is given.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub default_account: Option<String>,
+}
+
+impl Default for Concurrency {
+	fn default() -> Self {
+		let settings = Settings::default();
+		Self {
+			libraries: settings.library_concurrency,
+			assets: settings.asset_concurrency,
+		}
+	}
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		let settings = Settings::default();
+		Self {
+			version: CONFIG_VERSION,
+			data_dir: None,
+			launch: settings.launch,
+			mirrors: settings.mirrors,
+			maven_repositories: settings
+				.maven_repositories
+				.iter()
+				.map(|repository| repository.url().to_owned())
+				.collect(),
+			paper_api: settings.paper_api,
+			purpur_api: settings.purpur_api,
+			modrinth_api: settings.modrinth_api,
+			concurrency: Concurrency::default(),
+			manifest_ttl: settings.manifest_ttl.as_secs(),
+			kept_logs: settings.kept_logs,
+			http: settings.http,
+			default_account: None,
+		}
+	}
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+	Error::with_source(ErrorKind::InvalidConfig, message.into())
+}
+
+fn validate_url(field: &str, url: &str) -> Result<(), Error> {
+	reqwest::Url::parse(url)
+		.map(|_| ())
+		.map_err(|err| invalid(format!("{field}: `{url}` is not a valid url: {err}")))
+}
+
+/// Brings a config of any older version up to [`CONFIG_VERSION`].
+fn migrate(mut value: Value) -> Result<Value, Error> {
+	let object = value
+		.as_object_mut()
+		.ok_or_else(|| invalid("expected an object"))?;
+	let version = match object.get("version") {
+		Some(version) => {
+			version
+				.as_u64()
+				.ok_or_else(|| invalid("version: expected a number"))?
+		},
+		None => 0,
+	};
+
+	if version > CONFIG_VERSION {
+		return Err(invalid(format!(
+			"version {version} is newer than the supported version {CONFIG_VERSION}"
+		)));
+	}
+
+	for migration in &MIGRATIONS[version as usize..] {
+		migration(&mut value)?;
+	}
+	if let Some(object) = value.as_object_mut() {
+		object.insert("version".to_owned(), CONFIG_VERSION.into());
+	}
+
+	Ok(value)
+}
+
+impl Config {
+	/// `config.json` in the platform config directory, e.g.
+	/// `~/.config/mc/config.json` on Linux.
+	pub fn default_path() -> Result<PathBuf, Error> {
+		let dirs = ProjectDirs::from("one", "kkx", "mc")
+			.ok_or_else(|| Error::new(ErrorKind::DataDirUnavailable))?;
+
+		Ok(path!(dirs.config_dir() / "config.json"))
+	}
+
+	/// Reads, migrates and validates a config. A missing file is the default
+	/// config, an outdated one is rewritten in the current format.
+	pub async fn load(path: impl AsRef<Path>) -> Result<Config, Error> {
+		let path = path.as_ref();
+		let bytes = match fs::read(path).await {
+			Ok(bytes) => bytes,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+			Err(err) => return Err(err).with_path(path),
+		};
+
+		let value: Value = serde_json::from_slice(&bytes).with_path(path)?;
+		let outdated = value.get("version").and_then(Value::as_u64) != Some(CONFIG_VERSION);
+		let config: Config = serde_json::from_value(migrate(value).with_path(path)?)
+			.map_err(|err| invalid(err.to_string()))
+			.with_path(path)?;
+		config.validate().with_path(path)?;
+
+		if outdated {
+			config.save(path).await?;
+		}
+
+		Ok(config)
+	}
+
+	pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+		utils::write(&path, serde_json::to_string_pretty(self)?)
+			.await
+			.with_path(&path)
+	}
+
+	/// Checks values serde accepts but the launcher can not use.
+	pub fn validate(&self) -> Result<(), Error> {
+		if self.concurrency.libraries == 0 || self.concurrency.assets == 0 {
+			return Err(invalid("concurrency: must be at least 1"));
+		}
+
+		if self.kept_logs == 0 {
+			return Err(invalid("kept_logs: must be at least 1"));
+		}
+
+		if let (Some(min), Some(max)) = (self.launch.min_memory, self.launch.max_memory) {
+			if min > max {
+				return Err(invalid(format!(
+					"launch: min_memory {min} is larger than max_memory {max}"
+				)));
+			}
+		}
+
+		if let Some(data_dir) = &self.data_dir {
+			if !data_dir.is_absolute() {
+				return Err(invalid("data_dir: must be an absolute path"));
+			}
+		}
+
+		for mirror in &self.mirrors {
+			validate_url("mirrors", &mirror.from)?;
+			validate_url("mirrors", &mirror.to)?;
+		}
+		validate_url("paper_api", &self.paper_api)?;
+		validate_url("purpur_api", &self.purpur_api)?;
+		validate_url("modrinth_api", &self.modrinth_api)?;
+		for repository in &self.maven_repositories {
+			validate_url("maven_repositories", repository)?;
+		}
+		self.http.validate()?;
+
+		Ok(())
+	}
+
+	#[must_use]
+	pub fn settings(&self) -> Settings {
+		Settings {
+			library_concurrency: self.concurrency.libraries,
+			asset_concurrency: self.concurrency.assets,
+			manifest_ttl: Duration::from_secs(self.manifest_ttl),
+			kept_logs: self.kept_logs,
+			maven_repositories: self
+				.maven_repositories
+				.iter()
+				.map(Repository::new)
+				.collect(),
+			launch: self.launch.clone(),
+			mirrors: self.mirrors.clone(),
+			http: self.http.clone(),
+			paper_api: self.paper_api.clone(),
+			purpur_api: self.purpur_api.clone(),
+			modrinth_api: self.modrinth_api.clone(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+
+	#[test]
+	fn migrate_unversioned() {
+		let config =
+			migrate(json!({ "proxy": "socks5://localhost:1080", "manifest_ttl": 60 })).unwrap();
+		assert_eq!(
+			config,
+			json!({
+				"version": CONFIG_VERSION,
+				"http": { "proxy": "socks5://localhost:1080" },
+				"manifest_ttl": 60,
+			})
+		);
+		serde_json::from_value::<Config>(config).unwrap();
+	}
+
+	#[test]
+	fn migrate_keeps_http_settings() {
+		let config = migrate(json!({
+			"version": 1,
+			"proxy": "http://proxy",
+			"http": { "user_agent": "test" },
+		}))
+		.unwrap();
+		assert_eq!(
+			config["http"],
+			json!({ "user_agent": "test", "proxy": "http://proxy" })
+		);
+	}
+
+	#[test]
+	fn migrate_current_is_unchanged() {
+		let config = json!({ "version": CONFIG_VERSION, "manifest_ttl": 60 });
+		assert_eq!(migrate(config.clone()).unwrap(), config);
+	}
+
+	#[test]
+	fn reject_invalid() {
+		for config in [
+			json!([]),
+			json!({ "version": "1" }),
+			json!({ "version": CONFIG_VERSION + 1 }),
+			json!({ "proxy": "http://proxy", "http": "oops" }),
+		] {
+			let err = migrate(config.clone()).unwrap_err();
+			assert_eq!(err.kind(), ErrorKind::InvalidConfig, "{config}");
+		}
+	}
+}
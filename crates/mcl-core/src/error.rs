@@ -0,0 +1,261 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Stable classification of an [`Error`], meant for frontends to match on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+	Io,
+	Http,
+	Json,
+	Zip,
+	HashMismatch,
+	VersionNotFound,
+	InstanceAlreadyExists,
+	InstanceNotFound,
+	InvalidInstanceId,
+	ComponentAlreadyAdded,
+	ComponentNotFound,
+	MissingRequirement,
+	ComponentConflict,
+	IncompatibleVersion,
+	JavaAgentUnsupported,
+	AccountAlreadyExists,
+	AccountNotFound,
+	UnsupportedPlatform,
+	MissingNatives,
+	InvalidMavenCoordinate,
+	ArtifactNotFound,
+	DataDirUnavailable,
+	InvalidConfig,
+	EulaNotAccepted,
+	ModAlreadyExists,
+	ModNotFound,
+	InvalidMod,
+	InvalidModpack,
+	ModConflict,
+}
+
+impl fmt::Display for ErrorKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			ErrorKind::Io => "io error",
+			ErrorKind::Http => "http error",
+			ErrorKind::Json => "json error",
+			ErrorKind::Zip => "zip error",
+			ErrorKind::HashMismatch => "hash does not match",
+			ErrorKind::VersionNotFound => "version not found",
+			ErrorKind::InstanceAlreadyExists => "instance with this id already exists",
+			ErrorKind::InstanceNotFound => "instance not found",
+			ErrorKind::InvalidInstanceId => "instance id is not a plain directory name",
+			ErrorKind::ComponentAlreadyAdded => "component already exists on this instance",
+			ErrorKind::ComponentNotFound => "component not found on this instance",
+			ErrorKind::MissingRequirement => "component requirement not met",
+			ErrorKind::ComponentConflict => "conflicting components",
+			ErrorKind::IncompatibleVersion => "incompatible minecraft version",
+			ErrorKind::JavaAgentUnsupported => "java runtime does not support java agents",
+			ErrorKind::AccountAlreadyExists => "account with this username already exists",
+			ErrorKind::AccountNotFound => "account not found",
+			ErrorKind::UnsupportedPlatform => "unsupported platform",
+			ErrorKind::MissingNatives => "no natives for this platform",
+			ErrorKind::InvalidMavenCoordinate => "invalid maven coordinate",
+			ErrorKind::ArtifactNotFound => "artifact not found in any repository",
+			ErrorKind::DataDirUnavailable => "could not determine the data directory",
+			ErrorKind::InvalidConfig => "invalid configuration",
+			ErrorKind::EulaNotAccepted => "the minecraft eula has not been accepted",
+			ErrorKind::ModAlreadyExists => "mod already exists in this instance",
+			ErrorKind::ModNotFound => "mod not found in this instance",
+			ErrorKind::InvalidMod => "not a mod jar",
+			ErrorKind::InvalidModpack => "invalid modpack",
+			ErrorKind::ModConflict => "mod is incompatible with another mod",
+		})
+	}
+}
+
+/// What was being worked on when an [`Error`] occurred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Context {
+	Path(PathBuf),
+	Url(String),
+	Component(String),
+	Instance(String),
+	Library(String),
+}
+
+impl fmt::Display for Context {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Context::Path(path) => write!(f, "path `{}`", path.display()),
+			Context::Url(url) => write!(f, "url `{url}`"),
+			Context::Component(id) => write!(f, "component `{id}`"),
+			Context::Instance(id) => write!(f, "instance `{id}`"),
+			Context::Library(name) => write!(f, "library `{name}`"),
+		}
+	}
+}
+
+pub struct Error {
+	kind: ErrorKind,
+	context: Vec<Context>,
+	source: Option<Box<dyn std::error::Error + Send + Sync>>,
+	backtrace: Backtrace,
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+	#[must_use]
+	pub fn new(kind: ErrorKind) -> Self {
+		Self {
+			kind,
+			context: Vec::new(),
+			source: None,
+			backtrace: Backtrace::capture(),
+		}
+	}
+
+	#[must_use]
+	pub fn with_source(
+		kind: ErrorKind,
+		source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+	) -> Self {
+		Self {
+			source: Some(source.into()),
+			..Self::new(kind)
+		}
+	}
+
+	#[must_use]
+	pub fn kind(&self) -> ErrorKind {
+		self.kind
+	}
+
+	/// Context entries, innermost first.
+	#[must_use]
+	pub fn context(&self) -> &[Context] {
+		&self.context
+	}
+
+	/// Captured when the error is created if `RUST_BACKTRACE` or
+	/// `RUST_LIB_BACKTRACE` is set.
+	pub fn backtrace(&self) -> &Backtrace {
+		&self.backtrace
+	}
+
+	#[must_use]
+	pub fn context_with(mut self, context: Context) -> Self {
+		self.context.push(context);
+		self
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.kind)?;
+		if let Some(source) = &self.source {
+			write!(f, ": {source}")?;
+		}
+		for (i, context) in self.context.iter().enumerate() {
+			f.write_str(if i == 0 { " (" } else { ", " })?;
+			write!(f, "{context}")?;
+		}
+		if !self.context.is_empty() {
+			f.write_str(")")?;
+		}
+		Ok(())
+	}
+}
+
+impl fmt::Debug for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Error")
+			.field("kind", &self.kind)
+			.field("context", &self.context)
+			.field("source", &self.source)
+			.finish()?;
+		if self.backtrace.status() == BacktraceStatus::Captured {
+			write!(f, "\n\nstack backtrace:\n{}", self.backtrace)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		self.source
+			.as_deref()
+			.map(|source| source as &(dyn std::error::Error + 'static))
+	}
+}
+
+impl From<ErrorKind> for Error {
+	fn from(kind: ErrorKind) -> Self {
+		Self::new(kind)
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(err: std::io::Error) -> Self {
+		Self::with_source(ErrorKind::Io, err)
+	}
+}
+
+impl From<std::time::SystemTimeError> for Error {
+	fn from(err: std::time::SystemTimeError) -> Self {
+		Self::with_source(ErrorKind::Io, err)
+	}
+}
+
+impl From<reqwest::Error> for Error {
+	fn from(err: reqwest::Error) -> Self {
+		let url = err.url().map(ToString::to_string);
+		let error = Self::with_source(ErrorKind::Http, err);
+		match url {
+			Some(url) => error.context_with(Context::Url(url)),
+			None => error,
+		}
+	}
+}
+
+impl From<serde_json::Error> for Error {
+	fn from(err: serde_json::Error) -> Self {
+		Self::with_source(ErrorKind::Json, err)
+	}
+}
+
+impl From<zip::result::ZipError> for Error {
+	fn from(err: zip::result::ZipError) -> Self {
+		Self::with_source(ErrorKind::Zip, err)
+	}
+}
+
+/// Attaches [`Context`] to the error of a result.
+pub trait ResultExt<T> {
+	fn with_path(self, path: impl AsRef<Path>) -> Result<T>;
+	fn with_url(self, url: impl ToString) -> Result<T>;
+	fn with_component(self, id: impl ToString) -> Result<T>;
+	fn with_instance(self, id: impl ToString) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for std::result::Result<T, E> {
+	fn with_path(self, path: impl AsRef<Path>) -> Result<T> {
+		self.map_err(|err| {
+			err.into()
+				.context_with(Context::Path(path.as_ref().to_owned()))
+		})
+	}
+
+	fn with_url(self, url: impl ToString) -> Result<T> {
+		self.map_err(|err| err.into().context_with(Context::Url(url.to_string())))
+	}
+
+	fn with_component(self, id: impl ToString) -> Result<T> {
+		self.map_err(|err| err.into().context_with(Context::Component(id.to_string())))
+	}
+
+	fn with_instance(self, id: impl ToString) -> Result<T> {
+		self.map_err(|err| err.into().context_with(Context::Instance(id.to_string())))
+	}
+}
@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, ErrorKind, ResultExt};
+
+/// Sent with every request unless configured otherwise.
+pub const USER_AGENT: &str = concat!("mcl/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpSettings {
+	/// Proxy for all requests, an `http://`, `https://`, `socks5://` or
+	/// `socks5h://` URL.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub proxy: Option<String>,
+	/// PEM files with a root certificate each, trusted in addition to the
+	/// system ones.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub ca_certificates: Vec<PathBuf>,
+	pub user_agent: String,
+	/// Also makes the game use the proxy, through the `http.proxyHost` or
+	/// `socksProxyHost` system properties.
+	pub forward_proxy: bool,
+}
+
+impl Default for HttpSettings {
+	fn default() -> Self {
+		Self {
+			proxy: None,
+			ca_certificates: Vec::new(),
+			user_agent: USER_AGENT.to_owned(),
+			forward_proxy: false,
+		}
+	}
+}
+
+impl HttpSettings {
+	fn proxy_url(&self) -> Result<Option<reqwest::Url>, Error> {
+		let Some(proxy) = &self.proxy else {
+			return Ok(None);
+		};
+
+		let url = reqwest::Url::parse(proxy).map_err(|err| {
+			Error::with_source(
+				ErrorKind::InvalidConfig,
+				format!("proxy: `{proxy}` is not a valid url: {err}"),
+			)
+		})?;
+
+		if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+			return Err(Error::with_source(
+				ErrorKind::InvalidConfig,
+				format!("proxy: unsupported scheme `{}`", url.scheme()),
+			));
+		}
+
+		Ok(Some(url))
+	}
+
+	/// Checks the proxy URL and that the certificates can be read.
+	pub fn validate(&self) -> Result<(), Error> {
+		self.proxy_url()?;
+		for path in &self.ca_certificates {
+			std::fs::metadata(path).with_path(path)?;
+		}
+
+		Ok(())
+	}
+
+	pub fn client(&self) -> Result<reqwest::Client, Error> {
+		let mut builder = reqwest::Client::builder().user_agent(&self.user_agent);
+
+		if let Some(proxy) = self.proxy_url()? {
+			builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+		}
+
+		for path in &self.ca_certificates {
+			let pem = std::fs::read(path).with_path(path)?;
+			builder =
+				builder.add_root_certificate(reqwest::Certificate::from_pem(&pem).with_path(path)?);
+		}
+
+		Ok(builder.build()?)
+	}
+
+	/// System properties that make the game use the proxy, if it is forwarded.
+	#[must_use]
+	pub fn jvm_arguments(&self) -> Vec<String> {
+		let Ok(Some(proxy)) = self.proxy_url() else {
+			return Vec::new();
+		};
+		let (Some(host), true) = (proxy.host_str(), self.forward_proxy) else {
+			return Vec::new();
+		};
+
+		match proxy.scheme() {
+			"socks5" | "socks5h" => {
+				let port = proxy.port().unwrap_or(1080);
+				vec![
+					format!("-DsocksProxyHost={host}"),
+					format!("-DsocksProxyPort={port}"),
+				]
+			},
+			_ => {
+				let port = proxy.port_or_known_default().unwrap_or(80);
+				vec![
+					format!("-Dhttp.proxyHost={host}"),
+					format!("-Dhttp.proxyPort={port}"),
+					format!("-Dhttps.proxyHost={host}"),
+					format!("-Dhttps.proxyPort={port}"),
+				]
+			},
+		}
+	}
+}
@@ -1,36 +1,67 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
 use std::io;
 use std::mem::discriminant;
-use std::path::{Path, PathBuf};
+use std::path::{self, Path, PathBuf};
+use std::process::Stdio;
 
 use path_macro::path;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
+use tokio::process::{Child, Command};
 
+use crate::account::Account;
 use crate::component::minecraft::MinecraftClient;
-use crate::component::{self, Argument, Component, ComponentEnum, State, Version};
-use crate::utils::replace_placeholders;
-use crate::DIRS;
-
-// TODO: Replace all of these error types with a single one
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-	#[error("instance with this id already exists")]
-	InstanceAlreadyExists(),
-	#[error("component already exists on this instance")]
-	ComponentAlreadyAdded(),
-	#[error("io error: {0}")]
-	Io(#[from] io::Error),
-	#[error("serde json error: {0}")]
-	SerdeJson(#[from] serde_json::Error),
-	#[error("component error: {0}")]
-	ComponentError(#[from] component::Error),
+use crate::component::server::{self, MinecraftServer};
+use crate::component::{check_requirements, Argument, Component, ComponentEnum, State, Version};
+use crate::error::{Error, ErrorKind, ResultExt};
+use crate::launch::{self, LaunchOutcome, LaunchSettings};
+use crate::lockfile::{Change, Lockfile};
+use crate::mods::{Mod, ModLoader, Mods};
+use crate::store::{DedupeReport, LinkMode, Store};
+use crate::utils::{replace_placeholders, Hashes};
+use crate::Launcher;
+
+/// Used for a log config override on versions that do not name a config.
+const DEFAULT_LOG_CONFIG_ARGUMENT: &str = "-Dlog4j.configurationFile=${path}";
+
+/// The directory of instance `id`. The id has to be a single plain name, so
+/// the instance can not end up outside the instances directory.
+fn instance_dir(launcher: &Launcher, id: &str) -> Result<PathBuf, Error> {
+	let mut components = Path::new(id).components();
+	match (components.next(), components.next()) {
+		(Some(path::Component::Normal(name)), None) if name == id => {
+			Ok(path!(launcher.instances_dir() / id))
+		},
+		_ => Err(Error::new(ErrorKind::InvalidInstanceId)).with_instance(id),
+	}
+}
+
+/// Folders whose files [`Instance::dedupe`] moves into the store.
+const STORED_DIRS: &[(&str, LinkMode)] = &[
+	("mods", LinkMode::Shared),
+	("resourcepacks", LinkMode::Shared),
+	("shaderpacks", LinkMode::Shared),
+	("config", LinkMode::Private),
+];
+
+/// How a file at `path`, relative to the instance directory, is linked from
+/// the store: shared in the folders [`Instance::dedupe`] handles, private
+/// elsewhere so edits stay in the instance.
+#[must_use]
+pub fn link_mode(path: &Path) -> LinkMode {
+	STORED_DIRS
+		.iter()
+		.find(|(dir, _)| path.starts_with(dir))
+		.map_or(LinkMode::Private, |(_, mode)| *mode)
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Instance {
 	id: String,
 	components: Vec<ComponentEnum>,
+	#[serde(default)]
+	settings: LaunchSettings,
 }
 
 impl Instance {
@@ -40,8 +71,8 @@ impl Instance {
 	}
 
 	#[must_use]
-	pub fn get_path(&self) -> PathBuf {
-		path!(DIRS.data_dir() / "instances" / self.id)
+	pub fn get_path(&self, launcher: &Launcher) -> PathBuf {
+		path!(launcher.instances_dir() / self.id)
 	}
 
 	#[must_use]
@@ -49,118 +80,489 @@ impl Instance {
 		&self.components
 	}
 
-	pub fn add_component(&mut self, component: ComponentEnum) -> Result<(), Error> {
+	/// Whether this is a dedicated server instead of a client.
+	#[must_use]
+	pub fn is_server(&self) -> bool {
+		self.components
+			.iter()
+			.any(|component| matches!(component, ComponentEnum::MinecraftServer(_)))
+	}
+
+	/// The Minecraft version id, once the client or server component is
+	/// resolved or if it names a version.
+	#[must_use]
+	pub fn minecraft_version(&self) -> Option<&str> {
+		self.components.iter().find_map(|component| {
+			let (version, resolved) = match component {
+				ComponentEnum::MinecraftClient(client) => (&client.version, &client.resolved),
+				ComponentEnum::MinecraftServer(server) => (&server.version, &server.resolved),
+				_ => return None,
+			};
+
+			match (version, resolved) {
+				(_, Some(resolved)) => Some(resolved.as_str()),
+				(Version::Id(id), None) => Some(id.as_str()),
+				_ => None,
+			}
+		})
+	}
+
+	/// The loader mods have to be made for, if the instance has one.
+	#[must_use]
+	pub fn mod_loader(&self) -> Option<ModLoader> {
+		self.components.iter().find_map(|component| {
+			match component {
+				ComponentEnum::FabricLoader(_) | ComponentEnum::FabricServer(_) => {
+					Some(ModLoader::Fabric)
+				},
+				ComponentEnum::QuiltLoader(_) => Some(ModLoader::Quilt),
+				_ => None,
+			}
+		})
+	}
+
+	/// Launch settings as stored on the instance, without the launcher wide
+	/// defaults.
+	#[must_use]
+	pub fn get_settings(&self) -> &LaunchSettings {
+		&self.settings
+	}
+
+	pub fn set_settings(&mut self, settings: LaunchSettings) {
+		self.settings = settings;
+	}
+
+	/// Launch settings with unset values taken from the launcher defaults.
+	#[must_use]
+	pub fn launch_settings(&self, launcher: &Launcher) -> LaunchSettings {
+		self.settings.inherit(&launcher.settings().launch)
+	}
+
+	/// Adds a component if the resulting set satisfies every component's
+	/// requirements.
+	pub async fn add_component(
+		&mut self,
+		launcher: &Launcher,
+		component: ComponentEnum,
+	) -> Result<(), Error> {
 		if self
 			.components
 			.iter()
 			.any(|c| discriminant(&component) == discriminant(c))
 		{
-			return Err(Error::ComponentAlreadyAdded());
+			return Err(Error::new(ErrorKind::ComponentAlreadyAdded))
+				.with_component(component.id())
+				.with_instance(&self.id);
 		};
 
-		// check for conflicts
-
 		self.components.push(component);
 
+		let java = self.launch_settings(launcher).java();
+		if let Err(err) = check_requirements(launcher, &self.components, &java).await {
+			self.components.pop();
+			return Err(err).with_instance(&self.id);
+		}
+
 		Ok(())
 	}
 
-	pub async fn rename(&mut self, new_id: String) -> Result<(), Error> {
-		let new_path = path!(DIRS.data_dir() / "instances" / new_id);
-		if fs::metadata(&new_path).await.is_err() {
-			fs::rename(self.get_path(), &new_path).await?;
+	pub fn remove_component(&mut self, id: &str) -> Result<ComponentEnum, Error> {
+		let index = self
+			.components
+			.iter()
+			.position(|component| component.id() == id)
+			.ok_or_else(|| Error::new(ErrorKind::ComponentNotFound))
+			.with_component(id)
+			.with_instance(&self.id)?;
+
+		Ok(self.components.remove(index))
+	}
+
+	/// Moves the instance to the directory of `new_id` along with its
+	/// references in `store`. Call [`Store::save`] and [`Instance::save`]
+	/// afterwards.
+	pub async fn rename(
+		&mut self,
+		launcher: &Launcher,
+		store: &mut Store,
+		new_id: String,
+	) -> Result<(), Error> {
+		let new_path = instance_dir(launcher, &new_id)?;
+		if fs::metadata(&new_path).await.is_ok() {
+			return Err(Error::new(ErrorKind::InstanceAlreadyExists)).with_instance(new_id);
 		}
 
+		let old_path = self.get_path(launcher);
+		fs::rename(&old_path, &new_path)
+			.await
+			.with_path(&new_path)
+			.with_instance(&self.id)?;
+
+		store.move_references(&old_path, &new_path);
 		self.id = new_id;
 
 		Ok(())
 	}
 
-	pub async fn save(&self) -> Result<(), Error> {
-		fs::write(
-			path!(self.get_path() / "meta.json"),
-			serde_json::to_string_pretty(self)?,
-		)
-		.await?;
+	pub async fn save(&self, launcher: &Launcher) -> Result<(), Error> {
+		let path = path!(self.get_path(launcher) / "meta.json");
+		fs::write(&path, serde_json::to_string_pretty(self)?)
+			.await
+			.with_path(&path)
+			.with_instance(&self.id)?;
 
 		Ok(())
 	}
 
-	pub async fn new(id: String, minecraft_version: Version) -> Result<Instance, Error> {
-		if fs::metadata(path!(DIRS.data_dir() / "instances" / id))
-			.await
-			.is_ok()
-		{
-			return Err(Error::InstanceAlreadyExists());
+	/// Creates the directory of a new instance with `base` as its only
+	/// component. The instance is not saved yet.
+	pub async fn create(
+		launcher: &Launcher,
+		id: String,
+		base: ComponentEnum,
+	) -> Result<Instance, Error> {
+		let path = instance_dir(launcher, &id)?;
+		if fs::metadata(&path).await.is_ok() {
+			return Err(Error::new(ErrorKind::InstanceAlreadyExists)).with_instance(id);
 		};
 
-		fs::create_dir_all(path!(DIRS.data_dir() / "instances" / id)).await?;
-
-		let component: MinecraftClient = minecraft_version.into();
+		fs::create_dir_all(&path).await.with_path(&path)?;
 
 		let instance = Instance {
 			id,
-			components: vec![ComponentEnum::MinecraftClient(component)],
+			components: vec![base],
+			settings: LaunchSettings::default(),
 		};
 
 		Ok(instance)
 	}
 
+	pub async fn new(
+		launcher: &Launcher,
+		id: String,
+		minecraft_version: Version,
+	) -> Result<Instance, Error> {
+		let component: MinecraftClient = minecraft_version.into();
+		Self::create(launcher, id, ComponentEnum::MinecraftClient(component)).await
+	}
+
+	pub async fn new_server(
+		launcher: &Launcher,
+		id: String,
+		minecraft_version: Version,
+	) -> Result<Instance, Error> {
+		let component: MinecraftServer = minecraft_version.into();
+		Self::create(launcher, id, ComponentEnum::MinecraftServer(component)).await
+	}
+
+	/// Reads the `meta.json` at `path`. The instance has to be in the directory
+	/// named after its id, as that is where it is saved to and removed from.
 	pub async fn load(path: impl AsRef<Path>) -> Result<Instance, Error> {
-		Ok(serde_json::from_slice::<Instance>(&fs::read(path).await?)?)
+		let path = path.as_ref();
+		let bytes = fs::read(path).await.with_path(path)?;
+		let instance = serde_json::from_slice::<Instance>(&bytes).with_path(path)?;
+
+		let dir_name = path.parent().and_then(Path::file_name);
+		if dir_name != Some(OsStr::new(&instance.id)) {
+			return Err(Error::with_source(
+				ErrorKind::InvalidInstanceId,
+				"`meta.json` is not in the directory of the instance",
+			))
+			.with_path(path)
+			.with_instance(&instance.id);
+		}
+
+		Ok(instance)
+	}
+
+	pub async fn open(launcher: &Launcher, id: &str) -> Result<Instance, Error> {
+		let path = path!(instance_dir(launcher, id)? / "meta.json");
+		if fs::metadata(&path).await.is_err() {
+			return Err(Error::new(ErrorKind::InstanceNotFound)).with_instance(id);
+		}
+
+		Instance::load(path).await
+	}
+
+	/// The mods of the instance, see [`Mods::load`].
+	pub async fn mods(&self, launcher: &Launcher) -> Result<Mods, Error> {
+		Mods::load(self.get_path(launcher))
+			.await
+			.with_instance(&self.id)
 	}
 
-	pub async fn install(&self) -> Result<State, Error> {
+	/// Adds the mod at `source`, a URL verified with `hashes` or a local file,
+	/// and saves the mod list and the store.
+	pub async fn add_mod(
+		&self,
+		launcher: &Launcher,
+		source: &str,
+		hashes: Hashes,
+	) -> Result<Mod, Error> {
+		let mut mods = self.mods(launcher).await?;
+		let mut store = Store::open(launcher).await?;
+
+		let added = if source.starts_with("http://") || source.starts_with("https://") {
+			mods.add_url(launcher, &mut store, source, hashes)
+				.await?
+				.clone()
+		} else {
+			mods.add_file(&mut store, source).await?.clone()
+		};
+		store.save().await?;
+		mods.save().await?;
+
+		Ok(added)
+	}
+
+	/// Enables or disables the mod `file` and saves the mod list and the
+	/// store.
+	pub async fn set_mod_enabled(
+		&self,
+		launcher: &Launcher,
+		file: &str,
+		enabled: bool,
+	) -> Result<(), Error> {
+		let mut mods = self.mods(launcher).await?;
+		let mut store = Store::open(launcher).await?;
+
+		mods.set_enabled(&mut store, file, enabled).await?;
+		store.save().await?;
+		mods.save().await
+	}
+
+	/// Deletes the mod `file` and saves the mod list and the store.
+	pub async fn remove_mod(&self, launcher: &Launcher, file: &str) -> Result<Mod, Error> {
+		let mut mods = self.mods(launcher).await?;
+		let mut store = Store::open(launcher).await?;
+
+		let removed = mods.remove(&mut store, file).await?;
+		store.save().await?;
+		mods.save().await?;
+
+		Ok(removed)
+	}
+
+	/// Moves the mods, resource packs, shader packs and configs of the instance
+	/// into the store and links them back. Call [`Store::save`] afterwards.
+	pub async fn dedupe(
+		&self,
+		launcher: &Launcher,
+		store: &mut Store,
+	) -> Result<DedupeReport, Error> {
+		let mut report = DedupeReport::default();
+		for (dir, mode) in STORED_DIRS {
+			let dir_report = store
+				.dedupe(path!(self.get_path(launcher) / dir), *mode)
+				.await
+				.with_instance(&self.id)?;
+			report.files += dir_report.files;
+			report.saved_bytes += dir_report.saved_bytes;
+		}
+
+		Ok(report)
+	}
+
+	/// Deletes the instance directory and drops its references in `store`.
+	/// Call [`Store::save`] afterwards.
+	pub async fn remove(self, launcher: &Launcher, store: &mut Store) -> Result<(), Error> {
+		let path = self.get_path(launcher);
+		store.forget(&path);
+		fs::remove_dir_all(&path)
+			.await
+			.with_path(&path)
+			.with_instance(&self.id)?;
+
+		Ok(())
+	}
+
+	/// Pins tagged component versions that are not pinned yet, or all of them
+	/// if `update` is set, saving the instance if anything changed.
+	pub async fn resolve(&mut self, launcher: &Launcher, update: bool) -> Result<bool, Error> {
+		let changed = self.resolve_components(launcher, update).await?;
+		if changed {
+			self.save(launcher).await?;
+		}
+
+		Ok(changed)
+	}
+
+	/// Like [`Instance::resolve`] without saving the instance.
+	async fn resolve_components(
+		&mut self,
+		launcher: &Launcher,
+		update: bool,
+	) -> Result<bool, Error> {
+		let mut changed = false;
+		// The base game comes first, loaders may need its version
+		for i in 0..self.components.len() {
+			let minecraft_version = self.minecraft_version().map(str::to_owned);
+			let component = &mut self.components[i];
+			let id = component.id();
+			changed |= component
+				.resolve(launcher, update, minecraft_version.as_deref())
+				.await
+				.with_component(id)
+				.with_instance(&self.id)?;
+		}
+
+		Ok(changed)
+	}
+
+	#[must_use]
+	pub fn get_lockfile_path(&self, launcher: &Launcher) -> PathBuf {
+		path!(self.get_path(launcher) / "lock.json")
+	}
+
+	/// The lockfile written by the last successful install, if any.
+	pub async fn get_lockfile(&self, launcher: &Launcher) -> Result<Option<Lockfile>, Error> {
+		Lockfile::load(self.get_lockfile_path(launcher))
+			.await
+			.with_instance(&self.id)
+	}
+
+	/// Re-resolves tagged component versions, e.g. moving `latest` to the
+	/// newest version, installs the result and returns how the lockfile
+	/// changed.
+	pub async fn update(&mut self, launcher: &Launcher) -> Result<Vec<Change>, Error> {
+		let old = self.get_lockfile(launcher).await?.unwrap_or_default();
+
+		let result = self.install_resolved(launcher, true).await?;
+
+		Ok(old.diff(&result.lockfile))
+	}
+
+	pub async fn install(&mut self, launcher: &Launcher) -> Result<State, Error> {
+		self.install_resolved(launcher, false).await
+	}
+
+	/// Resolves and installs the components. New pins are saved only once they
+	/// are installed and in the lockfile, so a failed install leaves the
+	/// instance as it was.
+	async fn install_resolved(
+		&mut self,
+		launcher: &Launcher,
+		update: bool,
+	) -> Result<State, Error> {
+		let changed = self.resolve_components(launcher, update).await?;
+		check_requirements(
+			launcher,
+			&self.components,
+			&self.launch_settings(launcher).java(),
+		)
+		.await
+		.with_instance(&self.id)?;
+
 		let mut result = State {
-			classpath: Vec::new(),
-			main_class: "net.minecraft.client.main.Main".to_string(),
-			jvm_arguments: Vec::new(),
-			game_arguments: Vec::new(),
-			variables: HashMap::new(),
+			instance_dir: self.get_path(launcher),
+			..State::default()
 		};
 
-		for component in self.get_components() {
-			component.install(&mut result).await?;
+		result.variables.extend([
+			(
+				"game_directory".to_owned(),
+				self.get_path(launcher).to_string_lossy().into_owned(),
+			),
+			("launcher_name".to_owned(), "mcl".to_owned()),
+			(
+				"launcher_version".to_owned(),
+				env!("CARGO_PKG_VERSION").to_owned(),
+			),
+		]);
+
+		let mut components: Vec<&ComponentEnum> = self.components.iter().collect();
+		components.sort_by_key(|component| (component.layer(), component.id()));
+
+		for component in components {
+			component
+				.install(launcher, &mut result)
+				.await
+				.with_component(component.id())
+				.with_instance(&self.id)?;
+		}
+
+		result
+			.lockfile
+			.save(self.get_lockfile_path(launcher))
+			.await
+			.with_instance(&self.id)?;
+		if changed {
+			self.save(launcher).await?;
 		}
 
 		Ok(result)
 	}
 
-	// let _variables = HashMap::from([
-	//     ("auth_player_name", "33KK"),
-	//     ("version_name", &version.id), // full version
-	//     ("game_directory", &root.to_string_lossy()),
-	//     ("assets_root", &path!(root / "assets").to_string_lossy()),
-	//     ("assets_index_name", "1.19"),
-	//     // ("auth_uuid", "a340a0c3-225d-4518-9b47-d660fc50c1ff"),
-	//     // ("auth_access_token", ""),
-	//     // ("clientid", ""),
-	//     // ("auth_xuid", ""),
-	//     ("user_type", "offline"),
-	//     // ("version_type", ""),
-	//     // ("resolution_width", ""),
-	//     // ("resolution_height", ""),
-	//     (
-	//         "natives_directory",
-	//         &path!(root / "libraries").to_string_lossy(),
-	//     ),
-	//     ("launcher_name", "mc"),
-	//     ("launcher_version", "0.1"),
-	//     ("classpath", &build_classpath(version, root)),
-	//     ("path", ""),
-	// ]);
-
-	pub async fn launch(&self) -> Result<Vec<String>, Error> {
-		let mut result = self.install().await?;
+	/// Installs the instance and returns the arguments to pass to `java`,
+	/// including the heap size and extra arguments from the launch settings.
+	/// Clients need an account, servers launch without one.
+	pub async fn arguments(
+		&mut self,
+		launcher: &Launcher,
+		account: Option<&Account>,
+	) -> Result<Vec<String>, Error> {
+		if account.is_none() && !self.is_server() {
+			return Err(Error::new(ErrorKind::AccountNotFound)).with_instance(&self.id);
+		}
+
+		let mut result = self.install(launcher).await?;
+		let settings = self.launch_settings(launcher);
 
+		let separator = if cfg!(windows) { ";" } else { ":" };
 		result
 			.variables
-			.insert("classpath".to_owned(), result.classpath.join(":"));
+			.insert("classpath".to_owned(), result.classpath().join(separator));
+		if let Some(account) = account {
+			result.variables.extend([
+				("auth_player_name".to_owned(), account.username().to_owned()),
+				("auth_uuid".to_owned(), account.uuid().to_owned()),
+				(
+					"auth_access_token".to_owned(),
+					account.access_token().to_owned(),
+				),
+				("auth_session".to_owned(), account.access_token().to_owned()),
+				("user_type".to_owned(), account.user_type().to_owned()),
+				("user_properties".to_owned(), "{}".to_owned()),
+			]);
+		}
+
+		let log_config = match (&settings.log_config, &result.logging) {
+			(Some(path), logging) => {
+				Some((
+					logging
+						.as_ref()
+						.map_or(DEFAULT_LOG_CONFIG_ARGUMENT, |logging| {
+							logging.argument.as_str()
+						}),
+					path!(result.instance_dir / path),
+				))
+			},
+			(None, Some(logging)) => Some((logging.argument.as_str(), logging.path.clone())),
+			(None, None) => None,
+		};
+		if let Some((argument, path)) = log_config {
+			let argument = argument.replace("${path}", &path.to_string_lossy());
+			result.jvm_arguments.push(Argument::Single(argument));
+		}
+
+		result.jvm_arguments.extend(
+			launcher
+				.settings()
+				.http
+				.jvm_arguments()
+				.into_iter()
+				.chain(settings.jvm_arguments())
+				.map(Argument::Single),
+		);
+		result
+			.game_arguments
+			.extend(settings.game_arguments.into_iter().map(Argument::Single));
 
 		let mut args = Vec::new();
 		for argument in result
 			.jvm_arguments
 			.iter()
+			.chain([&Argument::Single(result.main_class.clone())])
 			.chain(result.game_arguments.iter())
 		{
 			match argument {
@@ -185,15 +587,222 @@ impl Instance {
 
 		Ok(args)
 	}
+
+	/// Builds the command that starts the game: `java`, or the configured
+	/// executable, prefixed with the wrapper command.
+	pub async fn command(
+		&mut self,
+		launcher: &Launcher,
+		account: Option<&Account>,
+	) -> Result<Command, Error> {
+		if self.is_server() && !server::eula_accepted(self.get_path(launcher)).await {
+			return Err(Error::new(ErrorKind::EulaNotAccepted)).with_instance(&self.id);
+		}
+
+		let arguments = self.arguments(launcher, account).await?;
+		let settings = self.launch_settings(launcher);
+
+		let mut command = match settings.wrapper.as_deref() {
+			Some([wrapper, wrapper_arguments @ ..]) => {
+				let mut command = Command::new(wrapper);
+				command.args(wrapper_arguments).arg(settings.java());
+				command
+			},
+			_ => Command::new(settings.java()),
+		};
+		command
+			.args(arguments)
+			.envs(&settings.environment)
+			.current_dir(self.get_path(launcher));
+
+		Ok(command)
+	}
+
+	pub async fn launch(
+		&mut self,
+		launcher: &Launcher,
+		account: Option<&Account>,
+	) -> Result<Child, Error> {
+		let path = self.get_path(launcher);
+
+		self.command(launcher, account)
+			.await?
+			.spawn()
+			.with_path(&path)
+			.with_instance(&self.id)
+	}
+
+	/// The program and arguments of [`Instance::command`], to show what would
+	/// be launched.
+	pub async fn command_line(
+		&mut self,
+		launcher: &Launcher,
+		account: Option<&Account>,
+	) -> Result<Vec<String>, Error> {
+		let command = self.command(launcher, account).await?;
+		let command = command.as_std();
+
+		Ok(std::iter::once(command.get_program())
+			.chain(command.get_args())
+			.map(|argument| argument.to_string_lossy().into_owned())
+			.collect())
+	}
+
+	/// Launches the game and waits for it to exit, writing its output to a
+	/// new log file in the instance and passing every line to `on_line`.
+	pub async fn run(
+		&mut self,
+		launcher: &Launcher,
+		account: Option<&Account>,
+		on_line: impl FnMut(&str),
+	) -> Result<LaunchOutcome, Error> {
+		let path = self.get_path(launcher);
+
+		let child = self
+			.command(launcher, account)
+			.await?
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped())
+			.spawn()
+			.with_path(&path)
+			.with_instance(&self.id)?;
+
+		launch::capture(child, &path, launcher.settings().kept_logs, on_line)
+			.await
+			.with_instance(&self.id)
+	}
 }
 
-pub async fn discover_instances() -> Result<Vec<Instance>, Error> {
+pub async fn discover_instances(launcher: &Launcher) -> Result<Vec<Instance>, Error> {
 	let mut instances: Vec<Instance> = Vec::new();
-	let mut dirs = fs::read_dir(path!(DIRS.data_dir() / "instances")).await?;
-
-	while let Ok(Some(dir)) = dirs.next_entry().await {
-		instances.push(Instance::load(path!(dir.path() / "meta.json")).await?);
+	let path = launcher.instances_dir();
+	let mut dirs = match fs::read_dir(&path).await {
+		Ok(dirs) => dirs,
+		Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(instances),
+		Err(err) => return Err(err).with_path(&path),
+	};
+
+	while let Some(dir) = dirs.next_entry().await.with_path(&path)? {
+		// Anything without a `meta.json` is not an instance
+		let meta = path!(dir.path() / "meta.json");
+		if fs::metadata(&meta).await.is_ok() {
+			instances.push(Instance::load(meta).await?);
+		}
 	}
 
 	Ok(instances)
 }
+
+/// Removes the instance `id` and drops its references from the store.
+pub async fn remove_instance(launcher: &Launcher, id: &str) -> Result<(), Error> {
+	let mut store = Store::open(launcher).await?;
+	Instance::open(launcher, id)
+		.await?
+		.remove(launcher, &mut store)
+		.await?;
+	store.save().await
+}
+
+/// Renames the instance `id` to `new_id`, moving its references in the store.
+pub async fn rename_instance(
+	launcher: &Launcher,
+	id: &str,
+	new_id: String,
+) -> Result<Instance, Error> {
+	let mut instance = Instance::open(launcher, id).await?;
+	let mut store = Store::open(launcher).await?;
+	instance.rename(launcher, &mut store, new_id).await?;
+	instance.save(launcher).await?;
+	store.save().await?;
+
+	Ok(instance)
+}
+
+/// Dedupes the instance `id`, or every instance, see [`Instance::dedupe`].
+/// The store is saved after each instance, so what was linked before one
+/// fails is kept.
+pub async fn dedupe_instances(
+	launcher: &Launcher,
+	id: Option<&str>,
+) -> Result<BTreeMap<String, DedupeReport>, Error> {
+	let instances = match id {
+		Some(id) => vec![Instance::open(launcher, id).await?],
+		None => discover_instances(launcher).await?,
+	};
+
+	let mut store = Store::open(launcher).await?;
+	let mut reports = BTreeMap::new();
+	for instance in instances {
+		let report = instance.dedupe(launcher, &mut store).await;
+		store.save().await?;
+		reports.insert(instance.id, report?);
+	}
+
+	Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::component::Tag;
+
+	#[tokio::test]
+	async fn reject_ids_outside_instances_dir() {
+		let data_dir = std::env::temp_dir().join(format!("mcl-instance-{}", std::process::id()));
+		let launcher = Launcher::new(&data_dir);
+
+		for id in ["", ".", "..", "../escaped", "a/b", "a/", "/tmp/escaped"] {
+			let err = Instance::new(&launcher, id.to_owned(), Version::Tag(Tag::Latest))
+				.await
+				.unwrap_err();
+			assert_eq!(err.kind(), ErrorKind::InvalidInstanceId, "{id}");
+			let err = Instance::open(&launcher, id).await.unwrap_err();
+			assert_eq!(err.kind(), ErrorKind::InvalidInstanceId, "{id}");
+		}
+
+		let mut instance = Instance::new(&launcher, "plain".to_owned(), Version::Tag(Tag::Latest))
+			.await
+			.unwrap();
+		let mut store = Store::open(&launcher).await.unwrap();
+		let err = instance
+			.rename(&launcher, &mut store, "../renamed".to_owned())
+			.await
+			.unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInstanceId);
+		assert_eq!(instance.get_id(), "plain");
+		assert!(path!(data_dir / "instances" / "plain").is_dir());
+		assert!(!path!(data_dir / "renamed").exists());
+
+		fs::remove_dir_all(&data_dir).await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn refuse_instances_in_another_directory() {
+		let data_dir = std::env::temp_dir().join(format!("mcl-moved-{}", std::process::id()));
+		let launcher = Launcher::new(&data_dir);
+
+		let instance = Instance::new(&launcher, "kept".to_owned(), Version::Tag(Tag::Latest))
+			.await
+			.unwrap();
+		instance.save(&launcher).await.unwrap();
+		let moved = path!(data_dir / "instances" / "moved");
+		fs::create_dir_all(&moved).await.unwrap();
+		fs::copy(
+			path!(data_dir / "instances" / "kept" / "meta.json"),
+			path!(moved / "meta.json"),
+		)
+		.await
+		.unwrap();
+
+		let err = Instance::open(&launcher, "moved").await.unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInstanceId);
+		let err = discover_instances(&launcher).await.unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInstanceId);
+		assert_eq!(
+			Instance::open(&launcher, "kept").await.unwrap().get_id(),
+			"kept"
+		);
+
+		fs::remove_dir_all(&data_dir).await.unwrap();
+	}
+}
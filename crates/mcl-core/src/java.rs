@@ -1,3 +1,11 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use path_macro::path;
+use serde::Serialize;
+use tokio::fs;
+use tokio::process::Command;
+
 pub const JAVA_DIRS: &[&str] = &[
 	"/usr/lib/jvm",
 	"/usr/lib64/jvm",
@@ -6,3 +14,78 @@ pub const JAVA_DIRS: &[&str] = &[
 	"/opt/jdk",
 	"/opt/jdks",
 ];
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct JavaInstallation {
+	pub path: PathBuf,
+	pub executable: PathBuf,
+	/// `JAVA_VERSION` from the installation's `release` file, if present.
+	pub version: Option<String>,
+}
+
+/// Finds Java installations in the well known [`JAVA_DIRS`].
+pub async fn discover() -> Vec<JavaInstallation> {
+	let mut installations = Vec::new();
+
+	for dir in JAVA_DIRS {
+		let mut entries = match fs::read_dir(dir).await {
+			Ok(entries) => entries,
+			Err(_) => continue,
+		};
+
+		while let Ok(Some(entry)) = entries.next_entry().await {
+			let path = entry.path();
+			let executable = path!(path / "bin" / "java");
+			if fs::metadata(&executable).await.is_err() {
+				continue;
+			}
+
+			// Symlinks such as `default-java` point at installations we already list
+			let path = fs::canonicalize(&path).await.unwrap_or(path);
+			if installations
+				.iter()
+				.any(|installation: &JavaInstallation| installation.path == path)
+			{
+				continue;
+			}
+
+			let version = fs::read_to_string(path!(path / "release"))
+				.await
+				.ok()
+				.and_then(|release| {
+					release.lines().find_map(|line| {
+						line.strip_prefix("JAVA_VERSION=")
+							.map(|version| version.trim_matches('"').to_owned())
+					})
+				});
+
+			installations.push(JavaInstallation {
+				executable: path!(path / "bin" / "java"),
+				path,
+				version,
+			});
+		}
+	}
+
+	installations
+}
+
+/// Whether the runtime can load agents with `-javaagent`, which needs the
+/// `java.instrument` module. Runtimes too old to know `--list-modules` always
+/// ship it.
+pub async fn supports_java_agent(executable: &Path) -> bool {
+	match Command::new(executable)
+		.arg("--list-modules")
+		.stderr(Stdio::null())
+		.output()
+		.await
+	{
+		Ok(output) if output.status.success() => {
+			String::from_utf8_lossy(&output.stdout)
+				.lines()
+				.any(|line| line.starts_with("java.instrument@"))
+		},
+		Ok(_) => true,
+		Err(_) => false,
+	}
+}
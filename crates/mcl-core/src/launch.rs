@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log4j::Line;
+use path_macro::path;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::process::Child;
+
+use crate::error::{Error, ErrorKind, ResultExt};
+use crate::utils::glob_match;
+
+pub mod log4j;
+
+/// How many lines of output a [`LaunchOutcome`] keeps.
+const LAST_LINES: usize = 50;
+
+/// How the game process is started. Instances inherit unset values from the
+/// launcher wide defaults in [`Settings::launch`](crate::Settings::launch).
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct LaunchSettings {
+	/// Initial heap size in MiB, passed as `-Xms`.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub min_memory: Option<u32>,
+	/// Maximum heap size in MiB, passed as `-Xmx`.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_memory: Option<u32>,
+	/// Added after the JVM arguments of the version. Can use the same `${...}`
+	/// placeholders as version arguments.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub jvm_arguments: Vec<String>,
+	/// Added after the game arguments of the version.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub game_arguments: Vec<String>,
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	pub environment: BTreeMap<String, String>,
+	/// Java executable to use instead of `java` from `PATH`.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub java: Option<PathBuf>,
+	/// log4j configuration to use instead of the one of the version, relative
+	/// to the instance directory.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub log_config: Option<PathBuf>,
+	/// Command the java invocation is prefixed with, like `gamemoderun` or
+	/// `prime-run`. An empty command disables an inherited wrapper.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub wrapper: Option<Vec<String>>,
+}
+
+impl LaunchSettings {
+	/// Fills in what is not set here from `defaults`. Arguments are appended to
+	/// the defaults and environment variables override them.
+	#[must_use]
+	pub fn inherit(&self, defaults: &LaunchSettings) -> LaunchSettings {
+		let mut environment = defaults.environment.clone();
+		environment.extend(self.environment.clone());
+
+		LaunchSettings {
+			min_memory: self.min_memory.or(defaults.min_memory),
+			max_memory: self.max_memory.or(defaults.max_memory),
+			jvm_arguments: [&defaults.jvm_arguments[..], &self.jvm_arguments[..]].concat(),
+			game_arguments: [&defaults.game_arguments[..], &self.game_arguments[..]].concat(),
+			environment,
+			java: self.java.clone().or_else(|| defaults.java.clone()),
+			log_config: self
+				.log_config
+				.clone()
+				.or_else(|| defaults.log_config.clone()),
+			wrapper: self.wrapper.clone().or_else(|| defaults.wrapper.clone()),
+		}
+	}
+
+	/// The java executable, falling back to `java` from `PATH`.
+	#[must_use]
+	pub fn java(&self) -> PathBuf {
+		self.java.clone().unwrap_or_else(|| PathBuf::from("java"))
+	}
+
+	/// JVM arguments for the heap size and the extra JVM arguments.
+	#[must_use]
+	pub fn jvm_arguments(&self) -> Vec<String> {
+		let mut arguments = Vec::new();
+		if let Some(min_memory) = self.min_memory {
+			arguments.push(format!("-Xms{min_memory}M"));
+		}
+		if let Some(max_memory) = self.max_memory {
+			arguments.push(format!("-Xmx{max_memory}M"));
+		}
+		arguments.extend(self.jvm_arguments.iter().cloned());
+		arguments
+	}
+}
+
+/// What happened to a game that was launched and has exited.
+#[derive(Serialize, Debug)]
+pub struct LaunchOutcome {
+	pub exit_code: Option<i32>,
+	/// Where the output of this launch was written.
+	pub log_file: PathBuf,
+	/// Crash report or JVM error log created while the game was running.
+	pub crash_report: Option<PathBuf>,
+	pub last_lines: Vec<String>,
+}
+
+impl LaunchOutcome {
+	#[must_use]
+	pub fn crashed(&self) -> bool {
+		self.crash_report.is_some() || self.exit_code != Some(0)
+	}
+}
+
+/// Creates a log file in `logs/mcl` of the instance, removing the oldest logs
+/// so at most `kept_logs` remain.
+async fn create_log_file(
+	instance_dir: &Path,
+	kept_logs: usize,
+) -> Result<(PathBuf, fs::File), Error> {
+	let dir = path!(instance_dir / "logs" / "mcl");
+	fs::create_dir_all(&dir).await.with_path(&dir)?;
+
+	let mut logs = Vec::new();
+	let mut entries = fs::read_dir(&dir).await.with_path(&dir)?;
+	while let Some(entry) = entries.next_entry().await.with_path(&dir)? {
+		logs.push(entry.path());
+	}
+	// Names are timestamps, so this is oldest first
+	logs.sort();
+
+	let excess = (logs.len() + 1).saturating_sub(kept_logs.max(1));
+	for log in logs.iter().take(excess) {
+		fs::remove_file(log).await.with_path(log)?;
+	}
+
+	let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+	let path = path!(dir / format!("{timestamp}.log"));
+	let file = fs::File::create(&path).await.with_path(&path)?;
+
+	Ok((path, file))
+}
+
+/// The newest crash report or `hs_err_pid` log modified since `since`.
+async fn find_crash_report(instance_dir: &Path, since: SystemTime) -> Option<PathBuf> {
+	let mut newest: Option<(SystemTime, PathBuf)> = None;
+
+	for (dir, pattern) in [
+		(path!(instance_dir / "crash-reports"), "*.txt"),
+		(instance_dir.to_owned(), "hs_err_pid*.log"),
+	] {
+		let Ok(mut entries) = fs::read_dir(&dir).await else {
+			continue;
+		};
+
+		while let Ok(Some(entry)) = entries.next_entry().await {
+			if !glob_match(pattern, &entry.file_name().to_string_lossy()) {
+				continue;
+			}
+			let Ok(modified) = entry
+				.metadata()
+				.await
+				.and_then(|metadata| metadata.modified())
+			else {
+				continue;
+			};
+
+			if modified >= since && newest.as_ref().is_none_or(|(newest, _)| modified > *newest) {
+				newest = Some((modified, entry.path()));
+			}
+		}
+	}
+
+	newest.map(|(_, path)| path)
+}
+
+struct Output<F> {
+	writer: BufWriter<fs::File>,
+	last_lines: VecDeque<String>,
+	on_line: F,
+}
+
+impl<F: FnMut(&str)> Output<F> {
+	async fn write(&mut self, line: &Line) -> std::io::Result<()> {
+		for line in line.to_string().lines() {
+			self.writer.write_all(line.as_bytes()).await?;
+			self.writer.write_all(b"\n").await?;
+
+			if self.last_lines.len() == LAST_LINES {
+				self.last_lines.pop_front();
+			}
+			self.last_lines.push_back(line.to_owned());
+			(self.on_line)(line);
+		}
+
+		Ok(())
+	}
+}
+
+/// Writes the stdout and stderr of `child` to a new log file in the instance
+/// until it exits, passing every line to `on_line`. log4j XML events on stdout
+/// are written as plain text.
+pub async fn capture(
+	mut child: Child,
+	instance_dir: &Path,
+	kept_logs: usize,
+	on_line: impl FnMut(&str),
+) -> Result<LaunchOutcome, Error> {
+	// File systems with coarse timestamps can date files created right after
+	// the launch slightly before it
+	let started = SystemTime::now() - Duration::from_secs(1);
+	let (log_file, file) = create_log_file(instance_dir, kept_logs).await?;
+
+	let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+		return Err(Error::with_source(
+			ErrorKind::Io,
+			"stdout and stderr of the game have to be piped",
+		));
+	};
+	let mut stdout = BufReader::new(stdout).split(b'\n');
+	let mut stderr = BufReader::new(stderr).split(b'\n');
+	let (mut stdout_open, mut stderr_open) = (true, true);
+
+	let mut parser = log4j::Parser::default();
+	let mut output = Output {
+		writer: BufWriter::new(file),
+		last_lines: VecDeque::with_capacity(LAST_LINES),
+		on_line,
+	};
+
+	while stdout_open || stderr_open {
+		let (segment, from_stdout) = tokio::select! {
+			segment = stdout.next_segment(), if stdout_open => (segment, true),
+			segment = stderr.next_segment(), if stderr_open => (segment, false),
+		};
+
+		let Some(segment) = segment.with_path(&log_file)? else {
+			if from_stdout {
+				stdout_open = false;
+			} else {
+				stderr_open = false;
+			}
+			continue;
+		};
+
+		let text = String::from_utf8_lossy(&segment);
+		let text = text.trim_end_matches('\r');
+		let line = if from_stdout {
+			parser.push(text)
+		} else {
+			Some(Line::Text(text.to_owned()))
+		};
+
+		if let Some(line) = line {
+			output.write(&line).await.with_path(&log_file)?;
+		}
+	}
+
+	if let Some(line) = parser.finish() {
+		output.write(&line).await.with_path(&log_file)?;
+	}
+	output.writer.flush().await.with_path(&log_file)?;
+
+	let status = child.wait().await?;
+
+	Ok(LaunchOutcome {
+		exit_code: status.code(),
+		log_file,
+		crash_report: find_crash_report(instance_dir, started).await,
+		last_lines: output.last_lines.into(),
+	})
+}
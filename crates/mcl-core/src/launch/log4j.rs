@@ -0,0 +1,207 @@
+//! Parses the `log4j:Event` XML the game prints when its logging config uses
+//! the XML layout, as the official launcher does.
+
+use std::fmt;
+
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+	pub logger: String,
+	/// Milliseconds since the Unix epoch.
+	pub timestamp: u64,
+	pub level: String,
+	pub thread: String,
+	pub message: String,
+	pub throwable: Option<String>,
+}
+
+impl fmt::Display for Event {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let seconds = self.timestamp / 1000 % (24 * 60 * 60);
+		write!(
+			f,
+			"[{:02}:{:02}:{:02}] [{}/{}]: {}",
+			seconds / 3600,
+			seconds / 60 % 60,
+			seconds % 60,
+			self.thread,
+			self.level,
+			self.message
+		)?;
+		if let Some(throwable) = &self.throwable {
+			write!(f, "\n{throwable}")?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+	Event(Event),
+	/// Output that is not part of an event, e.g. from before logging starts.
+	Text(String),
+}
+
+impl fmt::Display for Line {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Line::Event(event) => event.fmt(f),
+			Line::Text(text) => f.write_str(text),
+		}
+	}
+}
+
+/// Reassembles events from the lines they are printed over.
+#[derive(Debug, Default)]
+pub struct Parser {
+	event: Option<String>,
+}
+
+impl Parser {
+	/// Feeds one line of output, returning what it completes, if anything.
+	pub fn push(&mut self, line: &str) -> Option<Line> {
+		let buffer = match &mut self.event {
+			Some(buffer) => buffer,
+			None if line.trim_start().starts_with("<log4j:Event") => {
+				self.event.insert(String::new())
+			},
+			None => return Some(Line::Text(line.to_owned())),
+		};
+
+		buffer.push_str(line);
+		buffer.push('\n');
+
+		if !line.contains("</log4j:Event>") {
+			return None;
+		}
+
+		let xml = self.event.take().unwrap_or_default();
+		Some(parse_event(&xml).map_or_else(|| Line::Text(xml.trim_end().to_owned()), Line::Event))
+	}
+
+	/// Returns an event cut off by the end of the output as text.
+	pub fn finish(&mut self) -> Option<Line> {
+		self.event
+			.take()
+			.map(|xml| Line::Text(xml.trim_end().to_owned()))
+	}
+}
+
+fn unescape(text: &str) -> String {
+	text.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&quot;", "\"")
+		.replace("&apos;", "'")
+		.replace("&amp;", "&")
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+	let start = tag.find(&format!(" {name}=\""))? + name.len() + 3;
+	let end = start + tag[start..].find('"')?;
+	Some(unescape(&tag[start..end]))
+}
+
+fn element(xml: &str, name: &str) -> Option<String> {
+	let open = format!("<{name}>");
+	let start = xml.find(&open)? + open.len();
+	let end = start + xml[start..].find(&format!("</{name}>"))?;
+	let content = xml[start..end].trim();
+
+	Some(
+		match content
+			.strip_prefix("<![CDATA[")
+			.and_then(|content| content.strip_suffix("]]>"))
+		{
+			Some(content) => content.to_owned(),
+			None => unescape(content),
+		},
+	)
+}
+
+fn parse_event(xml: &str) -> Option<Event> {
+	let tag = &xml[..xml.find('>')?];
+
+	Some(Event {
+		logger: attribute(tag, "logger").unwrap_or_default(),
+		timestamp: attribute(tag, "timestamp")?.parse().ok()?,
+		level: attribute(tag, "level")?,
+		thread: attribute(tag, "thread").unwrap_or_default(),
+		message: element(xml, "log4j:Message").unwrap_or_default(),
+		throwable: element(xml, "log4j:Throwable"),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_events() {
+		let output = r#"Starting the game
+<log4j:Event logger="net.minecraft.client.Minecraft" timestamp="1700000000123" level="INFO" thread="Render thread">
+  <log4j:Message><![CDATA[Setting user: <Player>]]></log4j:Message>
+</log4j:Event>
+<log4j:Event logger="ezy" timestamp="1700000001000" level="ERROR" thread="Worker &amp; &quot;1&quot;">
+  <log4j:Message>Failed &lt;here&gt;</log4j:Message>
+  <log4j:Throwable><![CDATA[java.lang.RuntimeException: oops
+	at Main.main(Main.java:1)]]></log4j:Throwable>
+</log4j:Event>"#;
+
+		let mut parser = Parser::default();
+		let lines: Vec<Line> = output
+			.lines()
+			.filter_map(|line| parser.push(line))
+			.collect();
+		assert_eq!(parser.finish(), None);
+
+		assert_eq!(lines, [
+			Line::Text("Starting the game".to_owned()),
+			Line::Event(Event {
+				logger: "net.minecraft.client.Minecraft".to_owned(),
+				timestamp: 1_700_000_000_123,
+				level: "INFO".to_owned(),
+				thread: "Render thread".to_owned(),
+				message: "Setting user: <Player>".to_owned(),
+				throwable: None,
+			}),
+			Line::Event(Event {
+				logger: "ezy".to_owned(),
+				timestamp: 1_700_000_001_000,
+				level: "ERROR".to_owned(),
+				thread: "Worker & \"1\"".to_owned(),
+				message: "Failed <here>".to_owned(),
+				throwable: Some(
+					"java.lang.RuntimeException: oops\n\tat Main.main(Main.java:1)".to_owned()
+				),
+			}),
+		]);
+		assert_eq!(
+			lines[1].to_string(),
+			"[22:13:20] [Render thread/INFO]: Setting user: <Player>"
+		);
+	}
+
+	#[test]
+	fn invalid_and_unfinished_events_are_text() {
+		let mut parser = Parser::default();
+		assert_eq!(parser.push(r#"<log4j:Event level="INFO">"#), None);
+		assert_eq!(
+			parser.push("</log4j:Event>"),
+			Some(Line::Text(
+				"<log4j:Event level=\"INFO\">\n</log4j:Event>".to_owned()
+			))
+		);
+
+		assert_eq!(
+			parser.push(r#"<log4j:Event timestamp="1" level="INFO">"#),
+			None
+		);
+		assert_eq!(
+			parser.finish(),
+			Some(Line::Text(
+				"<log4j:Event timestamp=\"1\" level=\"INFO\">".to_owned()
+			))
+		);
+	}
+}
@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use directories::ProjectDirs;
+use path_macro::path;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::{Error, ErrorKind};
+use crate::http::{HttpSettings, USER_AGENT};
+use crate::launch::LaunchSettings;
+use crate::maven::Repository;
+
+/// Serves files from `to` instead of `from`. URLs starting with `from` have
+/// that prefix replaced.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Mirror {
+	pub from: String,
+	pub to: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+	/// How many libraries are downloaded at once.
+	pub library_concurrency: usize,
+	/// How many asset objects are downloaded at once.
+	pub asset_concurrency: usize,
+	/// How long a cached version manifest is used before refetching it.
+	pub manifest_ttl: Duration,
+	/// How many launch logs are kept per instance.
+	pub kept_logs: usize,
+	/// Searched for Maven artifacts after the repository a library names.
+	pub maven_repositories: Vec<Repository>,
+	/// Defaults for the launch settings of every instance.
+	pub launch: LaunchSettings,
+	/// Checked in order, the first matching mirror is used.
+	pub mirrors: Vec<Mirror>,
+	/// How the HTTP client is built and whether the game uses its proxy.
+	pub http: HttpSettings,
+	/// Base URL of the PaperMC download API.
+	pub paper_api: String,
+	/// Base URL of the Purpur download API.
+	pub purpur_api: String,
+	/// Base URL of the Modrinth API.
+	pub modrinth_api: String,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self {
+			library_concurrency: 5,
+			asset_concurrency: 30,
+			manifest_ttl: Duration::from_secs(30 * 60),
+			kept_logs: 10,
+			maven_repositories: vec![Repository::new("https://repo1.maven.org/maven2/")],
+			launch: LaunchSettings::default(),
+			mirrors: Vec::new(),
+			http: HttpSettings::default(),
+			paper_api: "https://api.papermc.io/v2".to_owned(),
+			purpur_api: "https://api.purpurmc.org/v2".to_owned(),
+			modrinth_api: "https://api.modrinth.com/v2".to_owned(),
+		}
+	}
+}
+
+impl Settings {
+	/// Rewrites `url` to the first mirror that covers it.
+	#[must_use]
+	pub fn mirror(&self, url: &str) -> String {
+		self.mirrors
+			.iter()
+			.find_map(|mirror| {
+				url.strip_prefix(&mirror.from)
+					.map(|rest| format!("{}{rest}", mirror.to))
+			})
+			.unwrap_or_else(|| url.to_owned())
+	}
+}
+
+/// Owns everything shared between instances: the data directory, the HTTP
+/// client and settings.
+#[derive(Debug, Clone)]
+pub struct Launcher {
+	data_dir: PathBuf,
+	http: reqwest::Client,
+	settings: Settings,
+}
+
+impl Launcher {
+	#[must_use]
+	pub fn new(data_dir: impl Into<PathBuf>) -> Self {
+		Self {
+			data_dir: data_dir.into(),
+			http: reqwest::Client::builder()
+				.user_agent(USER_AGENT)
+				.build()
+				.unwrap_or_default(),
+			settings: Settings::default(),
+		}
+	}
+
+	/// Uses the platform data directory, e.g. `~/.local/share/mc` on Linux.
+	pub fn from_project_dirs() -> Result<Self, Error> {
+		let dirs = ProjectDirs::from("one", "kkx", "mc")
+			.ok_or_else(|| Error::new(ErrorKind::DataDirUnavailable))?;
+
+		Ok(Self::new(dirs.data_dir()))
+	}
+
+	/// Uses the data directory, settings and HTTP client configured in
+	/// `config`, falling back to the platform data directory.
+	pub fn from_config(config: &Config) -> Result<Self, Error> {
+		let launcher = match &config.data_dir {
+			Some(data_dir) => Self::new(data_dir),
+			None => Self::from_project_dirs()?,
+		};
+
+		Ok(launcher
+			.with_http_client(config.http.client()?)
+			.with_settings(config.settings()))
+	}
+
+	#[must_use]
+	pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+		self.http = http;
+		self
+	}
+
+	#[must_use]
+	pub fn with_settings(mut self, settings: Settings) -> Self {
+		self.settings = settings;
+		self
+	}
+
+	#[must_use]
+	pub fn data_dir(&self) -> &Path {
+		&self.data_dir
+	}
+
+	#[must_use]
+	pub fn instances_dir(&self) -> PathBuf {
+		path!(self.data_dir / "instances")
+	}
+
+	#[must_use]
+	pub fn http(&self) -> &reqwest::Client {
+		&self.http
+	}
+
+	#[must_use]
+	pub fn settings(&self) -> &Settings {
+		&self.settings
+	}
+}
@@ -0,0 +1,18 @@
+pub mod account;
+pub mod component;
+pub mod config;
+pub mod error;
+pub mod http;
+pub mod instance;
+pub mod java;
+pub mod launch;
+pub mod launcher;
+pub mod lockfile;
+pub mod maven;
+pub mod modrinth;
+pub mod mods;
+pub mod store;
+pub mod utils;
+
+pub use error::{Error, ErrorKind};
+pub use launcher::{Launcher, Mirror, Settings};
@@ -0,0 +1,269 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::error::{Error, ResultExt};
+use crate::utils;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileKind {
+	Version,
+	Client,
+	Server,
+	Library,
+	AssetIndex,
+	LogConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LockedFile {
+	pub kind: FileKind,
+	pub name: String,
+	#[serde(with = "hex::serde")]
+	pub sha1: [u8; 20],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LockedComponent {
+	pub id: String,
+	pub version: String,
+	pub files: Vec<LockedFile>,
+}
+
+/// Exact versions and files used by the last install of an instance, stored
+/// as `lock.json` next to `meta.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lockfile {
+	pub components: Vec<LockedComponent>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "change", rename_all = "kebab-case")]
+pub enum Change {
+	Added {
+		component: String,
+		name: String,
+		to: String,
+	},
+	Removed {
+		component: String,
+		name: String,
+		from: String,
+	},
+	Changed {
+		component: String,
+		name: String,
+		from: String,
+		to: String,
+	},
+}
+
+impl Lockfile {
+	pub async fn load(path: impl AsRef<Path>) -> Result<Option<Lockfile>, Error> {
+		match fs::read(&path).await {
+			Ok(bytes) => serde_json::from_slice(&bytes).with_path(&path).map(Some),
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+			Err(err) => Err(err).with_path(&path),
+		}
+	}
+
+	pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+		utils::write(&path, serde_json::to_string_pretty(self)?)
+			.await
+			.with_path(&path)
+	}
+
+	#[must_use]
+	pub fn get(&self, id: &str) -> Option<&LockedComponent> {
+		self.components.iter().find(|component| component.id == id)
+	}
+
+	/// What changed going from `self` to `new`. Component versions are listed
+	/// under the name `version`, files by their name with hex SHA-1s.
+	#[must_use]
+	pub fn diff(&self, new: &Lockfile) -> Vec<Change> {
+		let mut changes = Vec::new();
+
+		for old_component in &self.components {
+			if new.get(&old_component.id).is_none() {
+				changes.push(Change::Removed {
+					component: old_component.id.clone(),
+					name: "version".to_owned(),
+					from: old_component.version.clone(),
+				});
+			}
+		}
+
+		for new_component in &new.components {
+			let component = new_component.id.clone();
+			let old_component = match self.get(&component) {
+				Some(old_component) => old_component,
+				None => {
+					changes.push(Change::Added {
+						component,
+						name: "version".to_owned(),
+						to: new_component.version.clone(),
+					});
+					continue;
+				},
+			};
+
+			if old_component.version != new_component.version {
+				changes.push(Change::Changed {
+					component: component.clone(),
+					name: "version".to_owned(),
+					from: old_component.version.clone(),
+					to: new_component.version.clone(),
+				});
+			}
+
+			let find = |files: &[LockedFile], file: &LockedFile| {
+				files
+					.iter()
+					.find(|other| other.kind == file.kind && other.name == file.name)
+					.map(|other| other.sha1)
+			};
+
+			for old_file in &old_component.files {
+				if find(&new_component.files, old_file).is_none() {
+					changes.push(Change::Removed {
+						component: component.clone(),
+						name: old_file.name.clone(),
+						from: hex::encode(old_file.sha1),
+					});
+				}
+			}
+
+			for new_file in &new_component.files {
+				match find(&old_component.files, new_file) {
+					None => {
+						changes.push(Change::Added {
+							component: component.clone(),
+							name: new_file.name.clone(),
+							to: hex::encode(new_file.sha1),
+						});
+					},
+					Some(sha1) if sha1 != new_file.sha1 => {
+						changes.push(Change::Changed {
+							component: component.clone(),
+							name: new_file.name.clone(),
+							from: hex::encode(sha1),
+							to: hex::encode(new_file.sha1),
+						});
+					},
+					Some(_) => {},
+				}
+			}
+		}
+
+		changes
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn file(kind: FileKind, name: &str, byte: u8) -> LockedFile {
+		LockedFile {
+			kind,
+			name: name.to_owned(),
+			sha1: [byte; 20],
+		}
+	}
+
+	fn component(id: &str, version: &str, files: Vec<LockedFile>) -> LockedComponent {
+		LockedComponent {
+			id: id.to_owned(),
+			version: version.to_owned(),
+			files,
+		}
+	}
+
+	#[test]
+	fn diff_unchanged() {
+		let lockfile = Lockfile {
+			components: vec![component("minecraft", "1.20.1", vec![file(
+				FileKind::Client,
+				"client.jar",
+				1,
+			)])],
+		};
+		assert_eq!(lockfile.diff(&lockfile.clone()), Vec::new());
+	}
+
+	#[test]
+	fn diff_components() {
+		let old = Lockfile {
+			components: vec![
+				component("minecraft", "1.20.1", Vec::new()),
+				component("fabric-loader", "0.14.21", Vec::new()),
+			],
+		};
+		let new = Lockfile {
+			components: vec![
+				component("minecraft", "1.20.2", Vec::new()),
+				component("quilt-loader", "0.20.0", Vec::new()),
+			],
+		};
+
+		assert_eq!(old.diff(&new), vec![
+			Change::Removed {
+				component: "fabric-loader".to_owned(),
+				name: "version".to_owned(),
+				from: "0.14.21".to_owned(),
+			},
+			Change::Changed {
+				component: "minecraft".to_owned(),
+				name: "version".to_owned(),
+				from: "1.20.1".to_owned(),
+				to: "1.20.2".to_owned(),
+			},
+			Change::Added {
+				component: "quilt-loader".to_owned(),
+				name: "version".to_owned(),
+				to: "0.20.0".to_owned(),
+			},
+		]);
+	}
+
+	#[test]
+	fn diff_files() {
+		let old = Lockfile {
+			components: vec![component("minecraft", "1.20.1", vec![
+				file(FileKind::Client, "client.jar", 1),
+				file(FileKind::Library, "org.ow2.asm:asm", 2),
+				file(FileKind::Library, "com.google.guava:guava", 3),
+			])],
+		};
+		let new = Lockfile {
+			components: vec![component("minecraft", "1.20.1", vec![
+				file(FileKind::Client, "client.jar", 1),
+				file(FileKind::Library, "org.ow2.asm:asm", 4),
+				// Same name, but a different kind of file
+				file(FileKind::AssetIndex, "com.google.guava:guava", 3),
+			])],
+		};
+
+		assert_eq!(old.diff(&new), vec![
+			Change::Removed {
+				component: "minecraft".to_owned(),
+				name: "com.google.guava:guava".to_owned(),
+				from: hex::encode([3; 20]),
+			},
+			Change::Changed {
+				component: "minecraft".to_owned(),
+				name: "org.ow2.asm:asm".to_owned(),
+				from: hex::encode([2; 20]),
+				to: hex::encode([4; 20]),
+			},
+			Change::Added {
+				component: "minecraft".to_owned(),
+				name: "com.google.guava:guava".to_owned(),
+				to: hex::encode([3; 20]),
+			},
+		]);
+	}
+}
@@ -1,25 +1,1026 @@
-#![feature(backtrace)]
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
-use directories::ProjectDirs;
-use instance::discover_instances;
-use lazy_static::lazy_static;
+use clap::{Parser, Subcommand, ValueEnum};
+use mcl_core::account::{Account, Accounts};
+use mcl_core::component::authlib_injector::AuthlibInjector;
+use mcl_core::component::minecraft::log4shell::Mitigation;
+use mcl_core::component::minecraft::version_manifest::{self, VersionFilter};
+use mcl_core::component::server::{self, ServerProperties};
+use mcl_core::component::{custom_version, minecraft, ComponentEnum, Version};
+use mcl_core::config::Config;
+use mcl_core::instance::{
+	dedupe_instances, discover_instances, remove_instance, rename_instance, Instance,
+};
+use mcl_core::launch::LaunchSettings;
+use mcl_core::lockfile::Change;
+use mcl_core::modrinth::{mrpack, Modrinth, Target, Update};
+use mcl_core::store::Store;
+use mcl_core::utils::{Hash, Hashes};
+use mcl_core::{java, Error, Launcher};
+use serde::Serialize;
+use serde_json::json;
 
-pub mod account;
-pub mod component;
-pub mod instance;
-pub mod java;
-pub mod utils;
+#[derive(Parser)]
+#[command(name = "mcl", version, about = "Minecraft launcher")]
+struct Cli {
+	/// Print machine readable JSON instead of text
+	#[arg(long, global = true)]
+	json: bool,
+	#[command(subcommand)]
+	command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+	/// Manage instances
+	#[command(subcommand)]
+	Instance(InstanceCommand),
+	/// Manage the components of an instance
+	#[command(subcommand)]
+	Component(ComponentCommand),
+	/// Download everything an instance needs to launch
+	Install { instance: String },
+	/// Move tagged component versions such as `latest` to the newest version
+	/// and show what changed
+	Update { instance: String },
+	/// Install and launch an instance
+	Launch {
+		instance: String,
+		/// Username of the account to play with
+		#[arg(long)]
+		account: Option<String>,
+		/// Print the command line instead of launching
+		#[arg(long)]
+		dry_run: bool,
+	},
+	/// Browse Minecraft versions
+	#[command(subcommand)]
+	Versions(VersionsCommand),
+	/// Find Java installations
+	#[command(subcommand)]
+	Java(JavaCommand),
+	/// Manage accounts
+	#[command(subcommand)]
+	Account(AccountCommand),
+	/// Show the launcher configuration
+	#[command(subcommand)]
+	Config(ConfigCommand),
+	/// Manage dedicated server instances
+	#[command(subcommand)]
+	Server(ServerCommand),
+	/// Manage the mods of an instance
+	#[command(subcommand)]
+	Mod(ModCommand),
+	/// Search and install mods from Modrinth
+	#[command(subcommand)]
+	Modrinth(ModrinthCommand),
+	/// Manage the store of files shared between instances
+	#[command(subcommand)]
+	Store(StoreCommand),
+}
+
+#[derive(Subcommand)]
+enum InstanceCommand {
+	Create {
+		id: String,
+		/// Minecraft version id, `latest`, `stable`, `latest-snapshot` or a
+		/// tag pinned to a date like `stable@2021-12-01`
+		#[arg(long, default_value = "stable")]
+		version: Version,
+		/// Create a dedicated server instead of a client
+		#[arg(long)]
+		server: bool,
+	},
+	List,
+	Remove {
+		id: String,
+	},
+	Rename {
+		id: String,
+		new_id: String,
+	},
+	Info {
+		id: String,
+	},
+	/// Change how an instance is launched
+	Configure {
+		id: String,
+		/// Start from the launcher defaults instead of the current settings
+		#[arg(long)]
+		reset: bool,
+		/// Initial heap size in MiB
+		#[arg(long)]
+		min_memory: Option<u32>,
+		/// Maximum heap size in MiB
+		#[arg(long)]
+		max_memory: Option<u32>,
+		/// Java executable to launch with
+		#[arg(long)]
+		java: Option<PathBuf>,
+		/// log4j config to use instead of the version's, relative to the
+		/// instance directory
+		#[arg(long)]
+		log_config: Option<PathBuf>,
+		/// Command to run java with, e.g. `gamemoderun`. An empty string
+		/// disables the default wrapper
+		#[arg(long)]
+		wrapper: Option<String>,
+		/// Extra JVM argument, can be repeated
+		#[arg(long = "jvm-arg", allow_hyphen_values = true)]
+		jvm_arguments: Vec<String>,
+		/// Extra game argument, can be repeated
+		#[arg(long = "game-arg", allow_hyphen_values = true)]
+		game_arguments: Vec<String>,
+		/// Environment variable as `KEY=VALUE`, can be repeated
+		#[arg(long = "env", value_parser = parse_env)]
+		environment: Vec<(String, String)>,
+	},
+}
+
+fn parse_env(s: &str) -> Result<(String, String), String> {
+	s.split_once('=')
+		.map(|(key, value)| (key.to_owned(), value.to_owned()))
+		.ok_or_else(|| format!("expected KEY=VALUE, found `{s}`"))
+}
+
+#[derive(Subcommand)]
+enum ComponentCommand {
+	Add {
+		instance: String,
+		component: ComponentKind,
+		#[arg(long, default_value = "stable")]
+		version: Version,
+	},
+	/// Add a version JSON, e.g. from OptiFine or LiteLoader, as a component
+	Import { instance: String, file: PathBuf },
+	Remove {
+		instance: String,
+		/// Component id, e.g. `fabric-loader`
+		component: String,
+	},
+}
+
+#[derive(Subcommand)]
+enum ModCommand {
+	/// List mods with the metadata of their jars
+	List {
+		instance: String,
+	},
+	/// Add a jar from a file or an http(s) URL
+	Add {
+		instance: String,
+		source: String,
+		/// Expected SHA-1 of a downloaded jar
+		#[arg(long, value_parser = parse_sha1)]
+		sha1: Option<Hash>,
+		/// Expected SHA-512 of a downloaded jar
+		#[arg(long, value_parser = parse_sha512)]
+		sha512: Option<Hash>,
+	},
+	Enable {
+		instance: String,
+		file: String,
+	},
+	Disable {
+		instance: String,
+		file: String,
+	},
+	Remove {
+		instance: String,
+		file: String,
+	},
+}
+
+#[derive(Subcommand)]
+enum ModrinthCommand {
+	/// Search mods, only those an instance can use if one is given
+	Search {
+		query: String,
+		#[arg(long)]
+		instance: Option<String>,
+		#[arg(long, default_value_t = 10)]
+		limit: u32,
+	},
+	/// List versions of a project the instance can use
+	Versions {
+		instance: String,
+		/// Project id or slug
+		project: String,
+	},
+	/// Install a project with its required dependencies
+	Install {
+		instance: String,
+		/// Project id or slug
+		project: String,
+		/// Version id or number instead of the newest compatible one
+		#[arg(long)]
+		version: Option<String>,
+	},
+	/// List mods with newer versions the instance can use, found by the hashes
+	/// of their jars
+	Updates { instance: String },
+	/// Create an instance from a `.mrpack` modpack
+	Import {
+		pack: PathBuf,
+		/// Instance id instead of the name of the pack
+		#[arg(long)]
+		id: Option<String>,
+	},
+	/// Update mods, all that have updates unless some are named
+	Update {
+		instance: String,
+		/// File names of the mods to update
+		files: Vec<String>,
+	},
+}
+
+fn parse_sha1(s: &str) -> Result<Hash, String> {
+	Hash::from_hex("sha1", s).ok_or_else(|| format!("`{s}` is not a hex SHA-1"))
+}
+
+fn parse_sha512(s: &str) -> Result<Hash, String> {
+	Hash::from_hex("sha512", s).ok_or_else(|| format!("`{s}` is not a hex SHA-512"))
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ComponentKind {
+	MinecraftClient,
+	AuthlibInjector,
+	FabricLoader,
+	QuiltLoader,
+	FabricServer,
+	PaperServer,
+	PurpurServer,
+}
+
+impl ComponentKind {
+	fn id(self) -> &'static str {
+		match self {
+			ComponentKind::MinecraftClient => "minecraft-client",
+			ComponentKind::AuthlibInjector => "authlib-injector",
+			ComponentKind::FabricLoader => "fabric-loader",
+			ComponentKind::QuiltLoader => "quilt-loader",
+			ComponentKind::FabricServer => "fabric-server",
+			ComponentKind::PaperServer => "paper-server",
+			ComponentKind::PurpurServer => "purpur-server",
+		}
+	}
+
+	fn with_version(self, version: Version) -> ComponentEnum {
+		match self {
+			ComponentKind::MinecraftClient => ComponentEnum::MinecraftClient(version.into()),
+			ComponentKind::AuthlibInjector => {
+				ComponentEnum::AuthlibInjector(AuthlibInjector { version })
+			},
+			ComponentKind::FabricLoader => ComponentEnum::FabricLoader(version.into()),
+			ComponentKind::QuiltLoader => ComponentEnum::QuiltLoader(version.into()),
+			ComponentKind::FabricServer => ComponentEnum::FabricServer(version.into()),
+			ComponentKind::PaperServer => ComponentEnum::PaperServer(version.into()),
+			ComponentKind::PurpurServer => ComponentEnum::PurpurServer(version.into()),
+		}
+	}
+}
+
+#[derive(Subcommand)]
+enum VersionsCommand {
+	List {
+		/// Only list versions of these types
+		#[arg(long = "type", value_enum)]
+		types: Vec<VersionType>,
+		/// Only list versions released on or after this date
+		#[arg(long)]
+		after: Option<String>,
+		/// Only list versions released before this date
+		#[arg(long)]
+		before: Option<String>,
+		/// Only list versions whose id matches this glob
+		#[arg(long)]
+		id: Option<String>,
+	},
+	/// Show the latest release and snapshot
+	Latest,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum VersionType {
+	Release,
+	Snapshot,
+	OldBeta,
+	OldAlpha,
+}
+
+impl From<VersionType> for version_manifest::VersionType {
+	fn from(type_: VersionType) -> Self {
+		match type_ {
+			VersionType::Release => version_manifest::VersionType::Release,
+			VersionType::Snapshot => version_manifest::VersionType::Snapshot,
+			VersionType::OldBeta => version_manifest::VersionType::OldBeta,
+			VersionType::OldAlpha => version_manifest::VersionType::OldAlpha,
+		}
+	}
+}
+
+#[derive(Subcommand)]
+enum JavaCommand {
+	List,
+}
+
+#[derive(Subcommand)]
+enum AccountCommand {
+	/// Add an offline account
+	Add {
+		username: String,
+	},
+	List,
+	Remove {
+		username: String,
+	},
+}
+
+#[derive(Subcommand)]
+enum ServerCommand {
+	/// Agree to the Minecraft EULA (https://aka.ms/MinecraftEULA), which the
+	/// server requires to start
+	AcceptEula { instance: String },
+	/// List `server.properties`, show one property or set it
+	Properties {
+		instance: String,
+		key: Option<String>,
+		value: Option<String>,
+	},
+}
+
+#[derive(Subcommand)]
+enum StoreCommand {
+	/// Move mods, resource packs, shader packs and configs into the store and
+	/// link them back
+	Dedupe {
+		/// Only this instance instead of all of them
+		instance: Option<String>,
+	},
+	/// Remove stored files no instance uses anymore
+	Gc,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+	/// Print the configuration with defaults filled in
+	Show,
+	/// Print where the configuration file is
+	Path,
+}
+
+fn output<T: Serialize>(json: bool, value: &T, text: impl FnOnce(&T)) -> Result<(), Error> {
+	if json {
+		println!("{}", serde_json::to_string_pretty(value)?);
+	} else {
+		text(value);
+	}
+
+	Ok(())
+}
+
+/// Prints `file: old -> new` with the new file name if it changes.
+fn print_update(update: &Update) {
+	let current = update
+		.current
+		.as_ref()
+		.map_or("unknown", |version| version.version_number.as_str());
+	print!(
+		"{}: {current} -> {}",
+		update.file, update.latest.version_number
+	);
+	match update.latest.primary_file() {
+		Some(file) if file.filename != update.file => println!(" ({})", file.filename),
+		_ => println!(),
+	}
+}
+
+async fn set_mod_enabled(
+	launcher: &Launcher,
+	json: bool,
+	instance: &str,
+	file: &str,
+	enabled: bool,
+) -> Result<(), Error> {
+	Instance::open(launcher, instance)
+		.await?
+		.set_mod_enabled(launcher, file, enabled)
+		.await?;
 
-lazy_static! {
-	static ref DIRS: ProjectDirs = ProjectDirs::from("one", "kkx", "mc").unwrap();
-	static ref HTTP: reqwest::Client = reqwest::Client::new();
+	output(json, &json!({ "file": file, "enabled": enabled }), |_| {
+		println!("{} {file}", if enabled { "enabled" } else { "disabled" });
+	})
+}
+
+async fn instance_command(
+	launcher: &Launcher,
+	json: bool,
+	command: InstanceCommand,
+) -> Result<(), Error> {
+	match command {
+		InstanceCommand::Create {
+			id,
+			version,
+			server,
+		} => {
+			let instance = if server {
+				Instance::new_server(launcher, id, version).await?
+			} else {
+				Instance::new(launcher, id, version).await?
+			};
+			instance.save(launcher).await?;
+			output(json, &instance, |instance| {
+				println!("created instance {}", instance.get_id());
+			})?;
+		},
+		InstanceCommand::List => {
+			let instances = discover_instances(launcher).await?;
+			output(json, &instances, |instances| {
+				for instance in instances {
+					println!("{}", instance.get_id());
+				}
+			})?;
+		},
+		InstanceCommand::Remove { id } => {
+			remove_instance(launcher, &id).await?;
+			output(json, &json!({ "removed": id }), |_| {
+				println!("removed instance {id}");
+			})?;
+		},
+		InstanceCommand::Rename { id, new_id } => {
+			let instance = rename_instance(launcher, &id, new_id).await?;
+			output(json, &instance, |instance| {
+				println!("renamed instance {id} to {}", instance.get_id());
+			})?;
+		},
+		InstanceCommand::Info { id } => {
+			let instance = Instance::open(launcher, &id).await?;
+			let info = json!({
+				"id": instance.get_id(),
+				"path": instance.get_path(launcher),
+				"components": instance.get_components(),
+			});
+			output(json, &info, |_| {
+				println!("id: {}", instance.get_id());
+				println!("path: {}", instance.get_path(launcher).display());
+				println!("components:");
+				for component in instance.get_components() {
+					println!("  {}", component.id());
+				}
+			})?;
+		},
+		InstanceCommand::Configure {
+			id,
+			reset,
+			min_memory,
+			max_memory,
+			java,
+			log_config,
+			wrapper,
+			jvm_arguments,
+			game_arguments,
+			environment,
+		} => {
+			let mut instance = Instance::open(launcher, &id).await?;
+			let mut settings = if reset {
+				LaunchSettings::default()
+			} else {
+				instance.get_settings().clone()
+			};
+
+			settings.min_memory = min_memory.or(settings.min_memory);
+			settings.max_memory = max_memory.or(settings.max_memory);
+			settings.java = java.or(settings.java);
+			settings.log_config = log_config.or(settings.log_config);
+			if let Some(wrapper) = wrapper {
+				settings.wrapper =
+					Some(wrapper.split_whitespace().map(ToOwned::to_owned).collect());
+			}
+			settings.jvm_arguments.extend(jvm_arguments);
+			settings.game_arguments.extend(game_arguments);
+			settings.environment.extend(environment);
+
+			instance.set_settings(settings);
+			instance.save(launcher).await?;
+			output(json, instance.get_settings(), |_| {
+				println!("updated launch settings of {id}");
+			})?;
+		},
+	}
+
+	Ok(())
+}
+
+async fn component_command(
+	launcher: &Launcher,
+	json: bool,
+	command: ComponentCommand,
+) -> Result<(), Error> {
+	match command {
+		ComponentCommand::Add {
+			instance,
+			component,
+			version,
+		} => {
+			let mut instance = Instance::open(launcher, &instance).await?;
+			instance
+				.add_component(launcher, component.with_version(version))
+				.await?;
+			instance.save(launcher).await?;
+			output(json, &instance, |instance| {
+				println!("added {} to {}", component.id(), instance.get_id());
+			})?;
+		},
+		ComponentCommand::Remove {
+			instance,
+			component,
+		} => {
+			let mut instance = Instance::open(launcher, &instance).await?;
+			instance.remove_component(&component)?;
+			instance.save(launcher).await?;
+			output(json, &instance, |instance| {
+				println!("removed {component} from {}", instance.get_id());
+			})?;
+		},
+		ComponentCommand::Import { instance, file } => {
+			let mut instance = Instance::open(launcher, &instance).await?;
+			custom_version::import(launcher, &mut instance, &file).await?;
+			instance.save(launcher).await?;
+			output(json, &instance, |instance| {
+				println!("imported {} into {}", file.display(), instance.get_id());
+			})?;
+		},
+	}
+
+	Ok(())
+}
+
+async fn versions_command(
+	launcher: &Launcher,
+	json: bool,
+	command: VersionsCommand,
+) -> Result<(), Error> {
+	match command {
+		VersionsCommand::List {
+			types,
+			after,
+			before,
+			id,
+		} => {
+			let manifest = minecraft::get_manifest(launcher).await?;
+			let filter = VersionFilter {
+				types: types.into_iter().map(Into::into).collect(),
+				released_after: after,
+				released_before: before,
+				id,
+			};
+			let versions = manifest.filter(&filter);
+			output(json, &versions, |versions| {
+				for version in versions {
+					println!("{} {}", version.id, version.release_time);
+				}
+			})?;
+		},
+		VersionsCommand::Latest => {
+			let manifest = minecraft::get_manifest(launcher).await?;
+			output(json, &manifest.latest, |latest| {
+				println!("release: {}", latest.release);
+				println!("snapshot: {}", latest.snapshot);
+			})?;
+		},
+	}
+
+	Ok(())
+}
+
+async fn account_command(
+	launcher: &Launcher,
+	json: bool,
+	command: AccountCommand,
+) -> Result<(), Error> {
+	match command {
+		AccountCommand::Add { username } => {
+			let mut accounts = Accounts::load(launcher).await?;
+			let account = Account::offline(username);
+			accounts.add(account.clone())?;
+			accounts.save(launcher).await?;
+			output(json, &account, |account| {
+				println!("added account {}", account.username());
+			})?;
+		},
+		AccountCommand::List => {
+			let accounts = Accounts::load(launcher).await?;
+			output(json, accounts.list(), |accounts| {
+				for account in accounts {
+					println!("{} {}", account.username(), account.uuid());
+				}
+			})?;
+		},
+		AccountCommand::Remove { username } => {
+			let mut accounts = Accounts::load(launcher).await?;
+			let account = accounts.remove(&username)?;
+			accounts.save(launcher).await?;
+			output(json, &account, |account| {
+				println!("removed account {}", account.username());
+			})?;
+		},
+	}
+
+	Ok(())
+}
+
+async fn server_command(
+	launcher: &Launcher,
+	json: bool,
+	command: ServerCommand,
+) -> Result<(), Error> {
+	match command {
+		ServerCommand::AcceptEula { instance } => {
+			let instance = Instance::open(launcher, &instance).await?;
+			server::accept_eula(instance.get_path(launcher)).await?;
+			output(json, &json!({ "eula": true }), |_| {
+				println!("accepted the eula for {}", instance.get_id());
+			})?;
+		},
+		ServerCommand::Properties {
+			instance,
+			key,
+			value,
+		} => {
+			let instance = Instance::open(launcher, &instance).await?;
+			let path = server::properties_path(instance.get_path(launcher));
+			let mut properties = ServerProperties::load(&path).await?;
+
+			match (key, value) {
+				(Some(key), Some(value)) => {
+					properties.set(&key, &value);
+					properties.save(&path).await?;
+					output(json, &json!({ key.clone(): value }), |_| {
+						println!("{key}={value}");
+					})?;
+				},
+				(Some(key), None) => {
+					let value = properties.get(&key);
+					output(json, &value, |value| {
+						println!("{key}={}", value.as_deref().unwrap_or_default());
+					})?;
+				},
+				(None, _) => {
+					let properties: BTreeMap<_, _> = properties.iter().collect();
+					output(json, &properties, |properties| {
+						for (key, value) in properties {
+							println!("{key}={value}");
+						}
+					})?;
+				},
+			}
+		},
+	}
+
+	Ok(())
+}
+
+async fn mod_command(launcher: &Launcher, json: bool, command: ModCommand) -> Result<(), Error> {
+	match command {
+		ModCommand::List { instance } => {
+			let instance = Instance::open(launcher, &instance).await?;
+			let mods = instance.mods(launcher).await?;
+			mods.save().await?;
+
+			output(json, &mods.list(), |mods| {
+				for listed in *mods {
+					let state = if listed.enabled { "" } else { " (disabled)" };
+					match &listed.metadata {
+						Some(metadata) => {
+							println!(
+								"{}{state}: {} {} [{}]",
+								listed.file,
+								metadata.name.as_deref().unwrap_or(&metadata.id),
+								metadata.version.as_deref().unwrap_or("?"),
+								metadata.loader
+							);
+						},
+						None => println!("{}{state}", listed.file),
+					}
+				}
+			})?;
+		},
+		ModCommand::Add {
+			instance,
+			source,
+			sha1,
+			sha512,
+		} => {
+			let hashes = Hashes::new(sha1.into_iter().chain(sha512));
+			let added = Instance::open(launcher, &instance)
+				.await?
+				.add_mod(launcher, &source, hashes)
+				.await?;
+			output(json, &added, |added| println!("added {}", added.file))?;
+		},
+		ModCommand::Enable { instance, file } => {
+			set_mod_enabled(launcher, json, &instance, &file, true).await?;
+		},
+		ModCommand::Disable { instance, file } => {
+			set_mod_enabled(launcher, json, &instance, &file, false).await?;
+		},
+		ModCommand::Remove { instance, file } => {
+			let removed = Instance::open(launcher, &instance)
+				.await?
+				.remove_mod(launcher, &file)
+				.await?;
+			output(json, &removed, |removed| {
+				println!("removed {}", removed.file)
+			})?;
+		},
+	}
+
+	Ok(())
+}
+
+async fn modrinth_command(
+	launcher: &Launcher,
+	json: bool,
+	command: ModrinthCommand,
+) -> Result<(), Error> {
+	match command {
+		ModrinthCommand::Search {
+			query,
+			instance,
+			limit,
+		} => {
+			let target = match instance {
+				Some(id) => {
+					let mut instance = Instance::open(launcher, &id).await?;
+					Some(Target::resolve(launcher, &mut instance).await?)
+				},
+				None => None,
+			};
+
+			let results = Modrinth::new(launcher)
+				.search(&query, target.as_ref(), limit, 0)
+				.await?;
+			output(json, &results, |results| {
+				for hit in &results.hits {
+					println!(
+						"{} ({}) by {}: {}",
+						hit.title, hit.slug, hit.author, hit.description
+					);
+				}
+			})?;
+		},
+		ModrinthCommand::Versions { instance, project } => {
+			let mut instance = Instance::open(launcher, &instance).await?;
+			let target = Target::resolve(launcher, &mut instance).await?;
+
+			let versions = Modrinth::new(launcher)
+				.project_versions(&project, Some(&target))
+				.await?;
+			output(json, &versions, |versions| {
+				for version in versions {
+					println!(
+						"{} {} ({}, {})",
+						version.id,
+						version.version_number,
+						version.version_type,
+						version.date_published
+					);
+				}
+			})?;
+		},
+		ModrinthCommand::Install {
+			instance,
+			project,
+			version,
+		} => {
+			let mut instance = Instance::open(launcher, &instance).await?;
+			let installed = Modrinth::new(launcher)
+				.install_project(&mut instance, &project, version.as_deref())
+				.await?;
+
+			output(json, &installed, |installed| {
+				for installed in installed {
+					println!("installed {}", installed.file);
+				}
+			})?;
+		},
+		ModrinthCommand::Updates { instance } => {
+			let mut instance = Instance::open(launcher, &instance).await?;
+			let updates = Modrinth::new(launcher)
+				.instance_updates(&mut instance)
+				.await?;
+
+			output(json, &updates, |updates| {
+				if updates.is_empty() {
+					println!("all mods are up to date");
+				}
+				for update in updates {
+					print_update(update);
+				}
+			})?;
+		},
+		ModrinthCommand::Update { instance, files } => {
+			let mut instance = Instance::open(launcher, &instance).await?;
+			let updates = Modrinth::new(launcher)
+				.update_instance(&mut instance, &files)
+				.await?;
+
+			output(json, &updates, |updates| {
+				if updates.is_empty() {
+					println!("all mods are up to date");
+				}
+				for update in updates {
+					print_update(update);
+				}
+			})?;
+		},
+		ModrinthCommand::Import { pack, id } => {
+			let imported = mrpack::import(launcher, &pack, id).await?;
+			output(json, &imported, |imported| {
+				println!(
+					"imported {} {} as {}: {} files, {} overrides",
+					imported.name,
+					imported.version,
+					imported.instance.get_id(),
+					imported.files,
+					imported.overrides
+				);
+			})?;
+		},
+	}
+
+	Ok(())
+}
+
+async fn store_command(
+	launcher: &Launcher,
+	json: bool,
+	command: StoreCommand,
+) -> Result<(), Error> {
+	match command {
+		StoreCommand::Dedupe { instance } => {
+			let reports = dedupe_instances(launcher, instance.as_deref()).await?;
+			output(json, &reports, |reports| {
+				for (id, report) in reports {
+					println!(
+						"{id}: {} files, {} KiB saved",
+						report.files,
+						report.saved_bytes / 1024
+					);
+				}
+			})?;
+		},
+		StoreCommand::Gc => {
+			let report = Store::open(launcher).await?.collect_garbage().await?;
+			output(json, &report, |report| {
+				println!(
+					"dropped {} references, removed {} files, freed {} KiB",
+					report.dropped_references,
+					report.removed_objects,
+					report.freed_bytes / 1024
+				);
+			})?;
+		},
+	}
+
+	Ok(())
+}
+
+async fn run(cli: Cli) -> Result<(), Error> {
+	let config_path = Config::default_path()?;
+	let config = Config::load(&config_path).await?;
+	let launcher = Launcher::from_config(&config)?;
+	let json = cli.json;
+
+	match cli.command {
+		Command::Instance(command) => instance_command(&launcher, json, command).await?,
+		Command::Component(command) => component_command(&launcher, json, command).await?,
+		Command::Install { instance } => {
+			let mut instance = Instance::open(&launcher, &instance).await?;
+			let state = instance.install(&launcher).await?;
+			output(json, &state, |state| {
+				match &state.log4shell_mitigation {
+					Some(Mitigation::PatchedConfig { log4j, config }) => {
+						println!("log4j {log4j} is vulnerable to Log4Shell, using {config}");
+					},
+					Some(Mitigation::FormatMsgNoLookups { log4j }) => {
+						println!(
+							"log4j {log4j} is vulnerable to Log4Shell, disabled message lookups"
+						);
+					},
+					None => {},
+				}
+				println!("installed {}", instance.get_id());
+			})?;
+		},
+		Command::Update { instance } => {
+			let mut instance = Instance::open(&launcher, &instance).await?;
+			let changes = instance.update(&launcher).await?;
+			output(json, &changes, |changes| {
+				if changes.is_empty() {
+					println!("{} is up to date", instance.get_id());
+				}
+				for change in changes {
+					match change {
+						Change::Added {
+							component,
+							name,
+							to,
+						} => println!("+ {component} {name} {to}"),
+						Change::Removed {
+							component,
+							name,
+							from,
+						} => println!("- {component} {name} {from}"),
+						Change::Changed {
+							component,
+							name,
+							from,
+							to,
+						} => println!("~ {component} {name} {from} -> {to}"),
+					}
+				}
+			})?;
+		},
+		Command::Launch {
+			instance,
+			account,
+			dry_run,
+		} => {
+			let mut instance = Instance::open(&launcher, &instance).await?;
+			let account = if instance.is_server() {
+				None
+			} else {
+				let accounts = Accounts::load(&launcher).await?;
+				let default = config.default_account.as_deref();
+				Some(accounts.find(account.as_deref(), default)?.clone())
+			};
+
+			if dry_run {
+				let command_line = instance.command_line(&launcher, account.as_ref()).await?;
+				output(json, &command_line, |command_line| {
+					println!("{}", command_line.join(" "));
+				})?;
+			} else {
+				let outcome = instance
+					.run(&launcher, account.as_ref(), |line| {
+						if !json {
+							println!("{line}");
+						}
+					})
+					.await?;
+				output(json, &outcome, |outcome| {
+					match outcome.exit_code {
+						Some(code) => println!("game exited with code {code}"),
+						None => println!("game was terminated"),
+					}
+					println!("log: {}", outcome.log_file.display());
+					if let Some(crash_report) = &outcome.crash_report {
+						println!("crash report: {}", crash_report.display());
+					}
+				})?;
+			}
+		},
+		Command::Versions(command) => versions_command(&launcher, json, command).await?,
+		Command::Java(JavaCommand::List) => {
+			let installations = java::discover().await;
+			output(json, &installations, |installations| {
+				for installation in installations {
+					println!(
+						"{} {}",
+						installation.version.as_deref().unwrap_or("unknown"),
+						installation.path.display()
+					);
+				}
+			})?;
+		},
+		Command::Account(command) => account_command(&launcher, json, command).await?,
+		Command::Server(command) => server_command(&launcher, json, command).await?,
+		Command::Config(ConfigCommand::Show) => {
+			output(json, &config, |config| {
+				println!(
+					"{}",
+					serde_json::to_string_pretty(config).unwrap_or_default()
+				);
+			})?;
+		},
+		Command::Config(ConfigCommand::Path) => {
+			output(json, &config_path, |path| println!("{}", path.display()))?;
+		},
+		Command::Mod(command) => mod_command(&launcher, json, command).await?,
+		Command::Modrinth(command) => modrinth_command(&launcher, json, command).await?,
+		Command::Store(command) => store_command(&launcher, json, command).await?,
+	}
+
+	Ok(())
 }
 
 #[tokio::main]
 async fn main() {
-	let instances = discover_instances().await.unwrap();
-
-	for instance in instances {
-		println!("java {}", instance.launch().await.unwrap().join(" "));
+	if let Err(err) = run(Cli::parse()).await {
+		eprintln!("error: {err}");
+		std::process::exit(1);
 	}
 }
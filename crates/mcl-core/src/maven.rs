@@ -0,0 +1,364 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use path_macro::path;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::error::{Context, Error, ErrorKind, ResultExt};
+use crate::utils::{self, download_file, Hash, Hashes};
+use crate::Launcher;
+
+/// A Maven artifact, written as
+/// `group:artifact:version[:classifier][@extension]`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Coordinate {
+	pub group: String,
+	pub artifact: String,
+	pub version: String,
+	pub classifier: Option<String>,
+	pub extension: String,
+}
+
+impl Coordinate {
+	/// Path of the artifact relative to the repository root.
+	#[must_use]
+	pub fn path(&self) -> String {
+		let classifier = self
+			.classifier
+			.as_ref()
+			.map(|classifier| format!("-{classifier}"))
+			.unwrap_or_default();
+
+		format!(
+			"{}/{artifact}/{version}/{artifact}-{version}{classifier}.{}",
+			self.group.replace('.', "/"),
+			self.extension,
+			artifact = self.artifact,
+			version = self.version,
+		)
+	}
+
+	/// `group:artifact[:classifier]`, which identifies the artifact across
+	/// versions.
+	#[must_use]
+	pub fn key(&self) -> String {
+		match &self.classifier {
+			Some(classifier) => format!("{}:{}:{classifier}", self.group, self.artifact),
+			None => format!("{}:{}", self.group, self.artifact),
+		}
+	}
+}
+
+impl FromStr for Coordinate {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || {
+			Error::new(ErrorKind::InvalidMavenCoordinate)
+				.context_with(Context::Library(s.to_owned()))
+		};
+
+		let (coordinate, extension) = match s.split_once('@') {
+			Some((coordinate, extension)) => (coordinate, extension),
+			None => (s, "jar"),
+		};
+
+		let mut parts = coordinate.split(':');
+		let mut next = || {
+			parts
+				.next()
+				.filter(|part| !part.is_empty())
+				.map(ToOwned::to_owned)
+		};
+		let group = next().ok_or_else(invalid)?;
+		let artifact = next().ok_or_else(invalid)?;
+		let version = next().ok_or_else(invalid)?;
+		let classifier = next();
+		if next().is_some() || extension.is_empty() {
+			return Err(invalid());
+		}
+
+		// Every part ends up in the path below the repository or the libraries
+		// directory, the group split at its dots
+		let parts = group
+			.split('.')
+			.chain([&*artifact, &*version, extension])
+			.chain(classifier.as_deref());
+		for part in parts {
+			if matches!(part, "" | "." | "..") || part.contains(['/', '\\']) {
+				return Err(invalid());
+			}
+		}
+
+		Ok(Self {
+			group,
+			artifact,
+			version,
+			classifier,
+			extension: extension.to_owned(),
+		})
+	}
+}
+
+impl TryFrom<String> for Coordinate {
+	type Error = Error;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		value.parse()
+	}
+}
+
+impl From<Coordinate> for String {
+	fn from(coordinate: Coordinate) -> Self {
+		coordinate.to_string()
+	}
+}
+
+impl fmt::Display for Coordinate {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}:{}:{}", self.group, self.artifact, self.version)?;
+		if let Some(classifier) = &self.classifier {
+			write!(f, ":{classifier}")?;
+		}
+		if self.extension != "jar" {
+			write!(f, "@{}", self.extension)?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Repository {
+	url: String,
+}
+
+impl Repository {
+	#[must_use]
+	pub fn new(url: impl Into<String>) -> Self {
+		let mut url = url.into();
+		if !url.ends_with('/') {
+			url.push('/');
+		}
+		Self { url }
+	}
+
+	#[must_use]
+	pub fn url(&self) -> &str {
+		&self.url
+	}
+
+	#[must_use]
+	pub fn artifact_url(&self, coordinate: &Coordinate) -> String {
+		format!("{}{}", self.url, coordinate.path())
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Artifact {
+	pub coordinate: Coordinate,
+	pub path: PathBuf,
+	pub sha1: [u8; 20],
+}
+
+/// Reads the `.sha512`, `.sha256` and `.sha1` sidecars of `url`, each holding
+/// the hex hash optionally followed by the file name. Repositories rarely have
+/// all of them, but without any the artifact can not be verified.
+async fn get_sidecar_hashes(launcher: &Launcher, url: &str) -> Result<Hashes, Error> {
+	let sidecars = ["sha512", "sha256", "sha1"].map(|algorithm| {
+		async move {
+			let url = format!("{url}.{algorithm}");
+			let text = launcher
+				.http()
+				.get(&url)
+				.send()
+				.await?
+				.error_for_status()?
+				.text()
+				.await?;
+
+			text.split_whitespace()
+				.next()
+				.and_then(|digest| Hash::from_hex(algorithm, digest))
+				.ok_or_else(|| Error::new(ErrorKind::HashMismatch))
+				.with_url(url)
+		}
+	});
+
+	let mut hashes = Hashes::default();
+	let mut last_error = None;
+	for sidecar in futures::future::join_all(sidecars).await {
+		match sidecar {
+			Ok(hash) => hashes.push(hash),
+			Err(err) => last_error = Some(err),
+		}
+	}
+
+	match last_error {
+		Some(err) if hashes.is_empty() => Err(err),
+		_ => Ok(hashes),
+	}
+}
+
+/// Where the sidecar hashes an artifact was verified with are kept, so a
+/// cached artifact can be checked without the repository.
+fn hashes_path(path: &Path) -> PathBuf {
+	let mut hashes_path = path.as_os_str().to_owned();
+	hashes_path.push(".hashes");
+	hashes_path.into()
+}
+
+/// The SHA-1 of the artifact at `path` if it matches the hashes it was
+/// downloaded with.
+async fn cached_sha1(path: &Path) -> Option<[u8; 20]> {
+	let hashes: Hashes = serde_json::from_slice(&fs::read(hashes_path(path)).await.ok()?).ok()?;
+	let bytes = fs::read(path).await.ok()?;
+	(!hashes.is_empty() && hashes.verify(&bytes)).then(|| openssl::sha::sha1(&bytes))
+}
+
+/// Downloads `coordinate` into the libraries directory from the first of
+/// `repositories` that has it. Without a known `sha1` the repository's hash
+/// sidecars are used to verify the download, and kept to verify it offline
+/// later.
+pub async fn resolve(
+	launcher: &Launcher,
+	repositories: &[Repository],
+	coordinate: &Coordinate,
+	sha1: Option<[u8; 20]>,
+) -> Result<Artifact, Error> {
+	let path = path!(launcher.data_dir() / "libraries" / coordinate.path());
+
+	let cached = match sha1 {
+		Some(sha1) => {
+			fs::read(&path)
+				.await
+				.is_ok_and(|bytes| Hash::Sha1(sha1).verify(&bytes))
+				.then_some(sha1)
+		},
+		None => cached_sha1(&path).await,
+	};
+	if let Some(sha1) = cached {
+		return Ok(Artifact {
+			coordinate: coordinate.clone(),
+			path,
+			sha1,
+		});
+	}
+
+	let mut last_error = None;
+	for repository in repositories {
+		let url = launcher
+			.settings()
+			.mirror(&repository.artifact_url(coordinate));
+		let expected = match sha1 {
+			Some(sha1) => Hashes::from(Hash::Sha1(sha1)),
+			None => {
+				match get_sidecar_hashes(launcher, &url).await {
+					Ok(hashes) => hashes,
+					Err(err) => {
+						last_error = Some(err);
+						continue;
+					},
+				}
+			},
+		};
+
+		match download_file(launcher.http(), &url, &path, expected.clone(), false).await {
+			Ok(_) => {
+				if sha1.is_none() {
+					let hashes_path = hashes_path(&path);
+					utils::write(&hashes_path, serde_json::to_vec(&expected)?)
+						.await
+						.with_path(&hashes_path)?;
+				}
+				let sha1 = match expected.sha1() {
+					Some(sha1) => sha1,
+					None => openssl::sha::sha1(&fs::read(&path).await.with_path(&path)?),
+				};
+
+				return Ok(Artifact {
+					coordinate: coordinate.clone(),
+					path,
+					sha1,
+				});
+			},
+			Err(err) if err.kind() == ErrorKind::HashMismatch => return Err(err),
+			Err(err) => last_error = Some(err),
+		}
+	}
+
+	let error = match last_error {
+		Some(err) => Error::with_source(ErrorKind::ArtifactNotFound, err),
+		None => Error::new(ErrorKind::ArtifactNotFound),
+	};
+	Err(error.context_with(Context::Library(coordinate.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_coordinate() {
+		let coordinate: Coordinate = "org.lwjgl:lwjgl:3.3.1:natives-linux".parse().unwrap();
+		assert_eq!(coordinate.group, "org.lwjgl");
+		assert_eq!(coordinate.artifact, "lwjgl");
+		assert_eq!(coordinate.version, "3.3.1");
+		assert_eq!(coordinate.classifier.as_deref(), Some("natives-linux"));
+		assert_eq!(coordinate.extension, "jar");
+		assert_eq!(coordinate.key(), "org.lwjgl:lwjgl:natives-linux");
+		assert_eq!(
+			coordinate.to_string(),
+			"org.lwjgl:lwjgl:3.3.1:natives-linux"
+		);
+
+		let coordinate: Coordinate = "net.fabricmc:intermediary:1.20.1@zip".parse().unwrap();
+		assert_eq!(coordinate.classifier, None);
+		assert_eq!(coordinate.extension, "zip");
+		assert_eq!(
+			coordinate.to_string(),
+			"net.fabricmc:intermediary:1.20.1@zip"
+		);
+	}
+
+	#[test]
+	fn reject_invalid_coordinate() {
+		for invalid in [
+			"",
+			"a",
+			"a:b",
+			"a::c",
+			"a:b:c:d:e",
+			"a:b:c@",
+			"..:b:c",
+			"a..b:c:d",
+			".a:b:c",
+			"a:..:c",
+			"a:b:..",
+			"a:b:c:..",
+			"a:b:c@..",
+			"a/b:c:d",
+			"a:b/../../x:c",
+			"a:b:c\\d",
+			"a:b:c@jar/x",
+		] {
+			let err = invalid.parse::<Coordinate>().unwrap_err();
+			assert_eq!(err.kind(), ErrorKind::InvalidMavenCoordinate, "{invalid}");
+		}
+	}
+
+	#[test]
+	fn coordinate_path() {
+		let coordinate: Coordinate = "org.ow2.asm:asm:9.5".parse().unwrap();
+		assert_eq!(coordinate.path(), "org/ow2/asm/asm/9.5/asm-9.5.jar");
+
+		let coordinate: Coordinate = "org.lwjgl:lwjgl:3.3.1:natives-linux@zip".parse().unwrap();
+		assert_eq!(
+			coordinate.path(),
+			"org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.zip"
+		);
+	}
+}
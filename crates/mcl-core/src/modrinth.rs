@@ -0,0 +1,866 @@
+//! Client for the [Modrinth API](https://docs.modrinth.com/api/), served from
+//! [`Settings::modrinth_api`](crate::Settings::modrinth_api).
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use path_macro::path;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::error::{Error, ErrorKind, ResultExt};
+use crate::instance::Instance;
+use crate::mods::{Mod, ModLoader, ModrinthSource, Mods};
+use crate::store::Store;
+use crate::utils::{Hash, Hashes};
+use crate::Launcher;
+
+pub mod mrpack;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchHit {
+	pub project_id: String,
+	pub slug: String,
+	pub title: String,
+	#[serde(default)]
+	pub description: String,
+	#[serde(default)]
+	pub author: String,
+	#[serde(default)]
+	pub downloads: u64,
+	/// Game versions the project supports.
+	#[serde(default)]
+	pub versions: Vec<String>,
+	#[serde(default)]
+	pub categories: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchResults {
+	pub hits: Vec<SearchHit>,
+	pub offset: u32,
+	pub limit: u32,
+	pub total_hits: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyType {
+	Required,
+	Optional,
+	Incompatible,
+	/// Shipped inside the jar, nothing to install.
+	Embedded,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Dependency {
+	pub version_id: Option<String>,
+	pub project_id: Option<String>,
+	pub file_name: Option<String>,
+	pub dependency_type: DependencyType,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VersionFile {
+	pub hashes: Hashes,
+	pub url: String,
+	pub filename: String,
+	#[serde(default)]
+	pub primary: bool,
+	#[serde(default)]
+	pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionType {
+	Release,
+	Beta,
+	Alpha,
+}
+
+impl fmt::Display for VersionType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			VersionType::Release => "release",
+			VersionType::Beta => "beta",
+			VersionType::Alpha => "alpha",
+		})
+	}
+}
+
+/// A version of a project, with its files.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Version {
+	pub id: String,
+	pub project_id: String,
+	pub name: String,
+	pub version_number: String,
+	pub game_versions: Vec<String>,
+	pub loaders: Vec<String>,
+	pub version_type: VersionType,
+	pub date_published: String,
+	pub files: Vec<VersionFile>,
+	#[serde(default)]
+	pub dependencies: Vec<Dependency>,
+}
+
+impl Version {
+	/// The file marked primary, else the first one as Modrinth does.
+	#[must_use]
+	pub fn primary_file(&self) -> Option<&VersionFile> {
+		self.files
+			.iter()
+			.find(|file| file.primary)
+			.or_else(|| self.files.first())
+	}
+
+	#[must_use]
+	pub fn supports(&self, target: &Target) -> bool {
+		self.game_versions.contains(&target.game_version)
+			&& self
+				.loaders
+				.iter()
+				.any(|loader| target.loaders.contains(loader))
+	}
+}
+
+/// What versions have to support to be installed into an instance.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+	pub game_version: String,
+	/// Modrinth loader names, any of which will do.
+	pub loaders: Vec<String>,
+}
+
+impl Target {
+	/// The resolved Minecraft version and mod loader of `instance`.
+	pub fn of(instance: &Instance) -> Result<Target, Error> {
+		let game_version = instance
+			.minecraft_version()
+			.ok_or_else(|| {
+				Error::with_source(
+					ErrorKind::MissingRequirement,
+					"the minecraft version of the instance is not resolved",
+				)
+			})
+			.with_instance(instance.get_id())?;
+		let loader = instance
+			.mod_loader()
+			.ok_or_else(|| {
+				Error::with_source(ErrorKind::MissingRequirement, "instance has no mod loader")
+			})
+			.with_instance(instance.get_id())?;
+
+		Ok(Target {
+			game_version: game_version.to_owned(),
+			loaders: loader_names(loader),
+		})
+	}
+
+	/// Resolves the components of `instance`, pinning those that are not yet,
+	/// and returns its target.
+	pub async fn resolve(launcher: &Launcher, instance: &mut Instance) -> Result<Target, Error> {
+		instance.resolve(launcher, false).await?;
+		Target::of(instance)
+	}
+}
+
+/// Names Modrinth uses for loaders whose mods work with `loader`.
+fn loader_names(loader: ModLoader) -> Vec<String> {
+	match loader {
+		ModLoader::Fabric => vec!["fabric".to_owned()],
+		// Quilt loads Fabric mods too
+		ModLoader::Quilt => vec!["quilt".to_owned(), "fabric".to_owned()],
+		ModLoader::Forge | ModLoader::LegacyForge => vec!["forge".to_owned()],
+	}
+}
+
+/// The newest release in `versions`, else the newest version. Modrinth lists
+/// versions newest first.
+fn newest(versions: Vec<Version>) -> Option<Version> {
+	let release = versions
+		.iter()
+		.position(|version| version.version_type == VersionType::Release);
+	versions.into_iter().nth(release.unwrap_or(0))
+}
+
+/// A newer version of an installed mod.
+#[derive(Serialize, Debug, Clone)]
+pub struct Update {
+	/// File name of the installed mod, without `.disabled`.
+	pub file: String,
+	/// The installed version, if Modrinth knows the jar.
+	pub current: Option<Version>,
+	pub latest: Version,
+}
+
+#[derive(Serialize)]
+struct HashesQuery<'a> {
+	hashes: &'a [String],
+	algorithm: &'static str,
+}
+
+#[derive(Serialize)]
+struct UpdatesQuery<'a> {
+	#[serde(flatten)]
+	hashes: HashesQuery<'a>,
+	loaders: &'a [String],
+	game_versions: [&'a str; 1],
+}
+
+/// The primary file of `version`, which needs a SHA-512 to go into the store.
+fn storable_file(version: &Version) -> Result<&VersionFile, Error> {
+	let file = version.primary_file().ok_or_else(|| {
+		Error::with_source(
+			ErrorKind::ArtifactNotFound,
+			format!("version {} has no files", version.id),
+		)
+	})?;
+	if file.hashes.get("sha512").is_none() {
+		return Err(Error::with_source(
+			ErrorKind::HashMismatch,
+			format!("no sha512 for {}", file.filename),
+		))
+		.with_url(&file.url);
+	}
+
+	Ok(file)
+}
+
+/// `project_id` can not be installed alongside `by`.
+fn conflict(project_id: &str, by: &str) -> Error {
+	Error::with_source(
+		ErrorKind::ModConflict,
+		format!("project {project_id} is incompatible with {by}"),
+	)
+}
+
+pub struct Modrinth<'a> {
+	launcher: &'a Launcher,
+}
+
+impl<'a> Modrinth<'a> {
+	#[must_use]
+	pub fn new(launcher: &'a Launcher) -> Self {
+		Self { launcher }
+	}
+
+	async fn get<T: DeserializeOwned>(
+		&self,
+		path: &str,
+		query: &[(&str, String)],
+	) -> Result<T, Error> {
+		let url = format!(
+			"{}{path}",
+			self.launcher.settings().modrinth_api.trim_end_matches('/')
+		);
+
+		Ok(self
+			.launcher
+			.http()
+			.get(&url)
+			.query(query)
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?)
+	}
+
+	async fn post<T: DeserializeOwned>(
+		&self,
+		path: &str,
+		body: &impl Serialize,
+	) -> Result<T, Error> {
+		let url = format!(
+			"{}{path}",
+			self.launcher.settings().modrinth_api.trim_end_matches('/')
+		);
+
+		Ok(self
+			.launcher
+			.http()
+			.post(&url)
+			.json(body)
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?)
+	}
+
+	/// Searches mods, only those available for `target` if given.
+	pub async fn search(
+		&self,
+		query: &str,
+		target: Option<&Target>,
+		limit: u32,
+		offset: u32,
+	) -> Result<SearchResults, Error> {
+		let mut facets = vec![vec!["project_type:mod".to_owned()]];
+		if let Some(target) = target {
+			facets.push(vec![format!("versions:{}", target.game_version)]);
+			facets.push(
+				target
+					.loaders
+					.iter()
+					.map(|loader| format!("categories:{loader}"))
+					.collect(),
+			);
+		}
+
+		self.get("/search", &[
+			("query", query.to_owned()),
+			("facets", serde_json::to_string(&facets)?),
+			("limit", limit.to_string()),
+			("offset", offset.to_string()),
+		])
+		.await
+	}
+
+	/// Versions of a project by id or slug, newest first, only those
+	/// supporting `target` if given.
+	pub async fn project_versions(
+		&self,
+		project: &str,
+		target: Option<&Target>,
+	) -> Result<Vec<Version>, Error> {
+		let mut query = Vec::new();
+		if let Some(target) = target {
+			query.push(("loaders", serde_json::to_string(&target.loaders)?));
+			query.push((
+				"game_versions",
+				serde_json::to_string(&[&target.game_version])?,
+			));
+		}
+
+		self.get(&format!("/project/{project}/version"), &query)
+			.await
+	}
+
+	/// A version by id.
+	pub async fn version(&self, id: &str) -> Result<Version, Error> {
+		self.get(&format!("/version/{id}"), &[]).await
+	}
+
+	/// A version of a project by its id or version number.
+	pub async fn project_version(&self, project: &str, version: &str) -> Result<Version, Error> {
+		self.get(&format!("/project/{project}/version/{version}"), &[])
+			.await
+	}
+
+	/// The newest version of a project supporting `target`, preferring
+	/// releases.
+	pub async fn latest_version(&self, project: &str, target: &Target) -> Result<Version, Error> {
+		newest(self.project_versions(project, Some(target)).await?).ok_or_else(|| {
+			Error::with_source(
+				ErrorKind::VersionNotFound,
+				format!(
+					"no version of `{project}` for {} on {}",
+					target.loaders.join("/"),
+					target.game_version
+				),
+			)
+		})
+	}
+
+	/// Downloads `file` into the store, verified with its hashes.
+	async fn download(&self, store: &Store, file: &VersionFile) -> Result<[u8; 64], Error> {
+		store
+			.download(
+				self.launcher.http(),
+				self.launcher.settings().mirror(&file.url),
+				file.hashes.clone(),
+			)
+			.await
+	}
+
+	/// Links `file` of `version`, which is in the store, into the mods folder.
+	async fn add_version(
+		store: &mut Store,
+		mods: &mut Mods,
+		version: &Version,
+		file: &VersionFile,
+		sha512: &[u8; 64],
+	) -> Result<Mod, Error> {
+		mods.add_object(store, sha512, &file.filename, Some(file.url.clone()))
+			.await?;
+
+		let installed = mods
+			.get_mut(&file.filename)
+			.ok_or_else(|| Error::with_source(ErrorKind::ModNotFound, file.filename.clone()))?;
+		installed.modrinth = Some(ModrinthSource {
+			project_id: version.project_id.clone(),
+			version_id: version.id.clone(),
+		});
+
+		Ok(installed.clone())
+	}
+
+	/// Downloads the primary file of `version` into the store, verified with
+	/// its SHA-512, and links it into the mods folder.
+	pub async fn install_file(
+		&self,
+		store: &mut Store,
+		mods: &mut Mods,
+		version: &Version,
+	) -> Result<Mod, Error> {
+		let file = storable_file(version)?;
+		let sha512 = self.download(store, file).await?;
+
+		Self::add_version(store, mods, version, file, &sha512).await
+	}
+
+	/// Installs `version` and, recursively, the newest versions of its required
+	/// dependencies that support `target`. Projects the instance already has
+	/// are skipped, and projects marked incompatible must be in neither the
+	/// instance nor the install. Returns the installed mods.
+	pub async fn install(
+		&self,
+		store: &mut Store,
+		mods: &mut Mods,
+		target: &Target,
+		version: Version,
+	) -> Result<Vec<Mod>, Error> {
+		let mut installed_projects: HashSet<String> = mods
+			.list()
+			.iter()
+			.filter_map(|listed| listed.modrinth.as_ref())
+			.map(|source| source.project_id.clone())
+			.collect();
+
+		if installed_projects.contains(&version.project_id) {
+			return Err(Error::with_source(
+				ErrorKind::ModAlreadyExists,
+				format!("project {} is already installed", version.project_id),
+			));
+		}
+
+		// Everything is resolved before anything is installed, so a conflict
+		// leaves the instance as it was
+		let mut resolved = Vec::new();
+		let mut queue = VecDeque::from([version]);
+		// Incompatible projects, mapped to the project that said so
+		let mut incompatible: HashMap<String, String> = HashMap::new();
+
+		while let Some(version) = queue.pop_front() {
+			if let Some(by) = incompatible.get(&version.project_id) {
+				return Err(conflict(&version.project_id, by));
+			}
+			if !installed_projects.insert(version.project_id.clone()) {
+				continue;
+			}
+
+			// A dependency added by hand or from another source
+			let present = version.primary_file().is_some_and(|file| {
+				mods.get(&file.filename).is_some()
+					|| file.hashes.iter().any(|hash| {
+						mods.list()
+							.iter()
+							.any(|listed| hash.digest() == listed.sha512)
+					})
+			});
+			if present && !resolved.is_empty() {
+				continue;
+			}
+
+			for dependency in &version.dependencies {
+				match dependency.dependency_type {
+					DependencyType::Required => {},
+					DependencyType::Incompatible => {
+						let project_id = match (&dependency.project_id, &dependency.version_id) {
+							(Some(project_id), _) => project_id.clone(),
+							(None, Some(version_id)) => self.version(version_id).await?.project_id,
+							(None, None) => continue,
+						};
+						let present = installed_projects.contains(&project_id)
+							|| queue.iter().any(|queued| queued.project_id == project_id);
+						if present {
+							return Err(conflict(&project_id, &version.project_id));
+						}
+						incompatible.insert(project_id, version.project_id.clone());
+						continue;
+					},
+					DependencyType::Optional | DependencyType::Embedded => continue,
+				}
+
+				let dependency = match (&dependency.version_id, &dependency.project_id) {
+					(Some(version_id), _) => {
+						let dependency = self.version(version_id).await?;
+						if !dependency.supports(target) {
+							return Err(Error::with_source(
+								ErrorKind::IncompatibleVersion,
+								format!(
+									"version {version_id} of `{}` is not for {} on {}",
+									dependency.project_id,
+									target.loaders.join("/"),
+									target.game_version
+								),
+							));
+						}
+						dependency
+					},
+					(None, Some(project_id)) if !installed_projects.contains(project_id) => {
+						self.latest_version(project_id, target).await?
+					},
+					_ => continue,
+				};
+				queue.push_back(dependency);
+			}
+
+			resolved.push(version);
+		}
+
+		let mut installed = Vec::new();
+		for version in &resolved {
+			installed.push(self.install_file(store, mods, version).await?);
+		}
+
+		Ok(installed)
+	}
+
+	/// Installs `version` of `project`, or its newest version supporting
+	/// `instance`, with its dependencies, see [`Modrinth::install`]. Mods
+	/// installed before a failing dependency are kept.
+	pub async fn install_project(
+		&self,
+		instance: &mut Instance,
+		project: &str,
+		version: Option<&str>,
+	) -> Result<Vec<Mod>, Error> {
+		let target = Target::resolve(self.launcher, instance).await?;
+		let version = match version {
+			Some(version) => {
+				let version = self.project_version(project, version).await?;
+				if !version.supports(&target) {
+					return Err(Error::with_source(
+						ErrorKind::IncompatibleVersion,
+						format!(
+							"{} supports {} on {}",
+							version.version_number,
+							version.loaders.join("/"),
+							version.game_versions.join(", ")
+						),
+					));
+				}
+				version
+			},
+			None => self.latest_version(project, &target).await?,
+		};
+
+		let mut mods = instance.mods(self.launcher).await?;
+		let mut store = Store::open(self.launcher).await?;
+		let installed = self.install(&mut store, &mut mods, &target, version).await;
+		store.save().await?;
+		mods.save().await?;
+
+		installed
+	}
+
+	/// Checks the mods of `instance` for updates, see
+	/// [`Modrinth::check_updates`], and saves the sources Modrinth knows.
+	pub async fn instance_updates(&self, instance: &mut Instance) -> Result<Vec<Update>, Error> {
+		let target = Target::resolve(self.launcher, instance).await?;
+		let mut mods = instance.mods(self.launcher).await?;
+		let updates = self.check_updates(&mut mods, &target).await?;
+		mods.save().await?;
+
+		Ok(updates)
+	}
+
+	/// Updates the mods `files` of `instance`, or all of them if there are
+	/// none, see [`Modrinth::apply_updates`]. Returns the updates applied.
+	pub async fn update_instance(
+		&self,
+		instance: &mut Instance,
+		files: &[String],
+	) -> Result<Vec<Update>, Error> {
+		let target = Target::resolve(self.launcher, instance).await?;
+		let mut mods = instance.mods(self.launcher).await?;
+		let mut updates = self.check_updates(&mut mods, &target).await?;
+		for file in files {
+			if mods.get(file).is_none() {
+				return Err(Error::with_source(ErrorKind::ModNotFound, file.clone()));
+			}
+		}
+		if !files.is_empty() {
+			updates.retain(|update| {
+				files.iter().any(|file| {
+					mods.get(file)
+						.is_some_and(|listed| listed.file == update.file)
+				})
+			});
+		}
+
+		let mut store = Store::open(self.launcher).await?;
+		let updated = self.apply_updates(&mut store, &mut mods, &updates).await;
+		store.save().await?;
+		mods.save().await?;
+		updated?;
+
+		Ok(updates)
+	}
+
+	/// Hashes the jars of `mods` and asks Modrinth which have newer versions
+	/// supporting `target`. Jars Modrinth knows get their source recorded,
+	/// call [`Mods::save`] afterwards to keep it.
+	pub async fn check_updates(
+		&self,
+		mods: &mut Mods,
+		target: &Target,
+	) -> Result<Vec<Update>, Error> {
+		mods.rehash().await?;
+		let hashes: Vec<String> = mods
+			.list()
+			.iter()
+			.map(|listed| hex::encode(listed.sha512))
+			.collect();
+		if hashes.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		let query = HashesQuery {
+			hashes: &hashes,
+			algorithm: "sha512",
+		};
+		let mut current: HashMap<String, Version> = self.post("/version_files", &query).await?;
+		let mut latest: HashMap<String, Version> = self
+			.post("/version_files/update", &UpdatesQuery {
+				hashes: query,
+				loaders: &target.loaders,
+				game_versions: [&target.game_version],
+			})
+			.await?;
+
+		let mut updates = Vec::new();
+		for hash in hashes {
+			let Some(listed) = mods.get_mut_by_sha512(&hash) else {
+				continue;
+			};
+			let current = current.remove(&hash);
+			if let Some(version) = &current {
+				listed.modrinth = Some(ModrinthSource {
+					project_id: version.project_id.clone(),
+					version_id: version.id.clone(),
+				});
+			}
+
+			let Some(latest) = latest.remove(&hash) else {
+				continue;
+			};
+			let newer = latest
+				.primary_file()
+				.is_some_and(|file| file.hashes.get("sha512") != Some(Hash::Sha512(listed.sha512)));
+			if newer {
+				updates.push(Update {
+					file: listed.file.clone(),
+					current,
+					latest,
+				});
+			}
+		}
+
+		Ok(updates)
+	}
+
+	/// Replaces mods with their updates. All files are downloaded and verified
+	/// first, so the mods folder is left alone if any of them fails. Should
+	/// replacing a jar fail, the ones replaced so far are put back.
+	pub async fn apply_updates(
+		&self,
+		store: &mut Store,
+		mods: &mut Mods,
+		updates: &[Update],
+	) -> Result<Vec<Mod>, Error> {
+		let mut downloaded = Vec::with_capacity(updates.len());
+		for update in updates {
+			let file = storable_file(&update.latest)?;
+			downloaded.push((file, self.download(store, file).await?));
+		}
+
+		// Jars added by hand are not in the store yet, keep them to roll back
+		let dir = mods.dir();
+		for update in updates {
+			let listed = mods
+				.get(&update.file)
+				.ok_or_else(|| Error::with_source(ErrorKind::ModNotFound, update.file.clone()))?;
+			if fs::metadata(store.object_path(&listed.sha512))
+				.await
+				.is_err()
+			{
+				store.insert_file(path!(dir / listed.file_name())).await?;
+			}
+		}
+
+		let mut removed = Vec::new();
+		let mut added = Vec::new();
+		let mut result = Ok(());
+		for (update, (file, sha512)) in updates.iter().zip(&downloaded) {
+			let old = match mods.remove(store, &update.file).await {
+				Ok(old) => old,
+				Err(err) => {
+					result = Err(err);
+					break;
+				},
+			};
+			let enabled = old.enabled;
+			removed.push(old);
+
+			match Self::add_version(store, mods, &update.latest, file, sha512).await {
+				Ok(new) => added.push(new),
+				Err(err) => {
+					result = Err(err);
+					break;
+				},
+			}
+			// Updates of disabled mods stay disabled
+			if !enabled {
+				if let Err(err) = mods.set_enabled(store, &file.filename, false).await {
+					result = Err(err);
+					break;
+				}
+				if let Some(new) = added.last_mut() {
+					new.enabled = false;
+				}
+			}
+		}
+
+		if let Err(err) = result {
+			for new in added.iter().rev() {
+				let _ = mods.remove(store, &new.file).await;
+			}
+			for old in removed.iter().rev() {
+				let _ = Self::restore(store, mods, old).await;
+			}
+			return Err(err);
+		}
+
+		Ok(added)
+	}
+
+	/// Puts a removed mod back from the store.
+	async fn restore(store: &mut Store, mods: &mut Mods, old: &Mod) -> Result<(), Error> {
+		mods.add_object(store, &old.sha512, &old.file, old.url.clone())
+			.await?;
+		mods.set_enabled(store, &old.file, old.enabled).await?;
+		if let Some(restored) = mods.get_mut(&old.file) {
+			*restored = old.clone();
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn version(id: &str, filename: &str, content: &[u8]) -> Version {
+		Version {
+			id: id.to_owned(),
+			project_id: format!("project-{id}"),
+			name: id.to_owned(),
+			version_number: id.to_owned(),
+			game_versions: vec!["1.20.1".to_owned()],
+			loaders: vec!["fabric".to_owned()],
+			version_type: VersionType::Release,
+			date_published: "2023-06-12T00:00:00Z".to_owned(),
+			files: vec![VersionFile {
+				hashes: Hash::Sha512(openssl::sha::sha512(content)).into(),
+				// Never fetched, the content is in the store already
+				url: format!("http://127.0.0.1:9/{filename}"),
+				filename: filename.to_owned(),
+				primary: true,
+				size: content.len() as u64,
+			}],
+			dependencies: Vec::new(),
+		}
+	}
+
+	async fn read_mods(mods: &Mods) -> Vec<(String, Vec<u8>)> {
+		let mut files = Vec::new();
+		for listed in mods.list() {
+			let path = path!(mods.dir() / listed.file_name());
+			files.push((listed.file.clone(), fs::read(&path).await.unwrap()));
+		}
+		files
+	}
+
+	#[tokio::test]
+	async fn apply_updates() {
+		let data_dir = std::env::temp_dir().join(format!("mcl-updates-{}", std::process::id()));
+		let launcher = Launcher::new(&data_dir);
+		let modrinth = Modrinth::new(&launcher);
+		let mut store = Store::open(&launcher).await.unwrap();
+
+		let instance_dir = path!(data_dir / "instances" / "a");
+		fs::create_dir_all(path!(instance_dir / "mods"))
+			.await
+			.unwrap();
+		for (file, content) in [("a-1.jar", "a1"), ("b-1.jar", "b1"), ("c.jar", "c")] {
+			fs::write(path!(instance_dir / "mods" / file), content)
+				.await
+				.unwrap();
+		}
+		let mut mods = Mods::load(&instance_dir).await.unwrap();
+		mods.set_enabled(&mut store, "b-1.jar", false)
+			.await
+			.unwrap();
+		let before = read_mods(&mods).await;
+
+		for content in ["a2", "b2"] {
+			let source = path!(data_dir / content);
+			fs::write(&source, content).await.unwrap();
+			store.insert_file(&source).await.unwrap();
+		}
+		let update = |file: &str, latest| {
+			Update {
+				file: file.to_owned(),
+				current: None,
+				latest,
+			}
+		};
+
+		// The second update would overwrite another mod, so the first one is
+		// rolled back
+		let updates = [
+			update("a-1.jar", version("a2", "a-2.jar", b"a2")),
+			update("b-1.jar", version("b2", "c.jar", b"b2")),
+		];
+		let err = modrinth
+			.apply_updates(&mut store, &mut mods, &updates)
+			.await
+			.unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::ModAlreadyExists);
+		assert_eq!(read_mods(&mods).await, before);
+		assert!(!mods.get("b-1.jar").unwrap().enabled);
+		assert!(!path!(instance_dir / "mods" / "a-2.jar").exists());
+
+		let updates = [
+			update("a-1.jar", version("a2", "a-2.jar", b"a2")),
+			update("b-1.jar", version("b2", "b-2.jar", b"b2")),
+		];
+		let added = modrinth
+			.apply_updates(&mut store, &mut mods, &updates)
+			.await
+			.unwrap();
+		assert_eq!(added.len(), 2);
+		let files: Vec<&str> = mods.list().iter().map(|listed| &*listed.file).collect();
+		assert_eq!(files, ["a-2.jar", "b-2.jar", "c.jar"]);
+		// Updates of disabled mods stay disabled
+		assert!(!mods.get("b-2.jar").unwrap().enabled);
+		assert_eq!(
+			mods.get("a-2.jar").unwrap().modrinth,
+			Some(ModrinthSource {
+				project_id: "project-a2".to_owned(),
+				version_id: "a2".to_owned(),
+			})
+		);
+
+		drop(store);
+		fs::remove_dir_all(&data_dir).await.unwrap();
+	}
+}
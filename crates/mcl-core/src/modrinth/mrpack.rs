@@ -0,0 +1,420 @@
+//! Modpacks in Modrinth's `.mrpack` format: a zip with `modrinth.index.json`
+//! listing the files to download, and `overrides/` and `client-overrides/`
+//! holding files to copy into the instance as they are.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use futures::{StreamExt, TryStreamExt};
+use path_macro::path;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use zip::ZipArchive;
+
+use crate::component::{ComponentEnum, Version};
+use crate::error::{Error, ErrorKind, ResultExt};
+use crate::instance::{self, Instance};
+use crate::store::Store;
+use crate::utils::{self, Hashes};
+use crate::Launcher;
+
+const INDEX: &str = "modrinth.index.json";
+
+/// Folders of the pack copied into the instance, later ones winning.
+const OVERRIDES: &[&str] = &["overrides/", "client-overrides/"];
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Support {
+	Required,
+	Optional,
+	Unsupported,
+}
+
+#[derive(Deserialize, Debug)]
+struct Env {
+	client: Support,
+}
+
+#[derive(Deserialize, Debug)]
+struct PackFile {
+	/// Relative to the instance directory.
+	path: String,
+	hashes: Hashes,
+	/// Missing if both sides need the file.
+	env: Option<Env>,
+	/// Mirrors of the same file, tried in order.
+	downloads: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Index {
+	format_version: u32,
+	game: String,
+	version_id: String,
+	name: String,
+	files: Vec<PackFile>,
+	/// `minecraft` and the mod loader, mapped to their versions.
+	dependencies: BTreeMap<String, String>,
+}
+
+/// What [`import`] created.
+#[derive(Serialize, Debug)]
+pub struct Imported {
+	pub instance: Instance,
+	/// Name of the pack.
+	pub name: String,
+	/// Version of the pack.
+	pub version: String,
+	/// Files downloaded, without those only servers use.
+	pub files: usize,
+	/// Files copied from the overrides.
+	pub overrides: usize,
+}
+
+/// `path` if it stays inside the instance directory.
+fn relative_path(path: &str) -> Result<PathBuf, Error> {
+	let relative = Path::new(path);
+	if !utils::is_contained(relative) {
+		return Err(Error::with_source(
+			ErrorKind::InvalidModpack,
+			format!("`{path}` is outside the instance directory"),
+		));
+	}
+
+	Ok(relative.to_owned())
+}
+
+/// The Minecraft version and mod loader component the pack depends on.
+fn components(
+	dependencies: &BTreeMap<String, String>,
+) -> Result<(Version, Option<ComponentEnum>), Error> {
+	let minecraft = dependencies
+		.get("minecraft")
+		.ok_or_else(|| Error::with_source(ErrorKind::InvalidModpack, "no minecraft version"))?;
+
+	let mut loader = None;
+	for (id, version) in dependencies {
+		let version = Version::Id(version.clone());
+		let component = match id.as_str() {
+			"minecraft" => continue,
+			"fabric-loader" => ComponentEnum::FabricLoader(version.into()),
+			"quilt-loader" => ComponentEnum::QuiltLoader(version.into()),
+			_ => {
+				return Err(Error::with_source(
+					ErrorKind::InvalidModpack,
+					format!("{id} is not supported"),
+				))
+			},
+		};
+		if loader.replace(component).is_some() {
+			return Err(Error::with_source(
+				ErrorKind::InvalidModpack,
+				"more than one mod loader",
+			));
+		}
+	}
+
+	Ok((Version::Id(minecraft.clone()), loader))
+}
+
+/// An instance id from the name of the pack.
+fn instance_id(name: &str) -> String {
+	let id: String = name
+		.chars()
+		.map(|c| {
+			if c.is_alphanumeric() || "-_. ".contains(c) {
+				c
+			} else {
+				'-'
+			}
+		})
+		.collect();
+	let id = id.trim().trim_start_matches('.');
+
+	if id.is_empty() {
+		"modpack".to_owned()
+	} else {
+		id.to_owned()
+	}
+}
+
+/// Downloads `file` into the store from the first of its mirrors that works,
+/// returning the object and the URL it came from.
+async fn download<'f>(
+	launcher: &Launcher,
+	store: &Store,
+	file: &'f PackFile,
+) -> Result<([u8; 64], &'f str), Error> {
+	let mut last_error = Error::with_source(
+		ErrorKind::InvalidModpack,
+		format!("no downloads for `{}`", file.path),
+	);
+	for url in &file.downloads {
+		match store
+			.download(
+				launcher.http(),
+				launcher.settings().mirror(url),
+				file.hashes.clone(),
+			)
+			.await
+		{
+			Ok(sha512) => return Ok((sha512, url)),
+			Err(err) => last_error = err,
+		}
+	}
+
+	Err(last_error)
+}
+
+/// Copies `overrides/` and then `client-overrides/` into `dir`, returning how
+/// many files were copied.
+async fn extract_overrides(
+	store: &mut Store,
+	archive: &mut ZipArchive<File>,
+	dir: &Path,
+) -> Result<usize, Error> {
+	let mut copied = 0;
+
+	for prefix in OVERRIDES {
+		for i in 0..archive.len() {
+			let (path, bytes) = {
+				let mut entry = archive.by_index(i)?;
+				let Some(name) = entry.name().strip_prefix(prefix) else {
+					continue;
+				};
+				if entry.is_dir() || name.is_empty() {
+					continue;
+				}
+
+				let path = path!(dir / relative_path(name)?);
+				let mut bytes = Vec::new();
+				entry.read_to_end(&mut bytes)?;
+				(path, bytes)
+			};
+
+			// A downloaded file may be hardlinked from the store, writing through
+			// it would change the object for every instance
+			match fs::remove_file(&path).await {
+				Err(err) if err.kind() != io::ErrorKind::NotFound => {
+					return Err(err).with_path(&path)
+				},
+				_ => {},
+			}
+			store.forget(&path);
+
+			utils::write(&path, bytes).await.with_path(&path)?;
+			copied += 1;
+		}
+	}
+
+	Ok(copied)
+}
+
+/// Adds the loader, downloads and links the files and copies the overrides,
+/// returning how many overrides were copied.
+async fn populate(
+	launcher: &Launcher,
+	store: &mut Store,
+	archive: &mut ZipArchive<File>,
+	instance: &mut Instance,
+	loader: Option<ComponentEnum>,
+	files: &[(PathBuf, &PackFile)],
+) -> Result<usize, Error> {
+	if let Some(loader) = loader {
+		instance.add_component(launcher, loader).await?;
+	}
+	instance.save(launcher).await?;
+	let dir = instance.get_path(launcher);
+
+	let objects = futures::stream::iter(
+		files
+			.iter()
+			.map(|(_, file)| download(launcher, store, file)),
+	)
+	.buffered(launcher.settings().library_concurrency)
+	.try_collect::<Vec<_>>()
+	.await?;
+
+	for ((path, _), (sha512, _)) in files.iter().zip(&objects) {
+		store
+			.link(sha512, path!(dir / path), instance::link_mode(path))
+			.await?;
+	}
+
+	let overrides = extract_overrides(store, archive, &dir).await?;
+
+	// Remember where mods came from, the rest are picked up as added by hand
+	let mut mods = instance.mods(launcher).await?;
+	for ((path, _), (_, url)) in files.iter().zip(&objects) {
+		let Ok(name) = path.strip_prefix("mods") else {
+			continue;
+		};
+		if let Some(listed) = mods.get_mut(&name.to_string_lossy()) {
+			listed.url = Some((*url).to_owned());
+		}
+	}
+	mods.save().await?;
+
+	Ok(overrides)
+}
+
+/// Creates an instance from the `.mrpack` at `pack`, named `id` or after the
+/// pack. Files only servers use are skipped, the others are downloaded into
+/// the store, verified with their hashes, and linked into the instance. The
+/// instance is removed again if anything fails, while what went into the
+/// store is kept for the next attempt.
+pub async fn import(
+	launcher: &Launcher,
+	pack: impl AsRef<Path>,
+	id: Option<String>,
+) -> Result<Imported, Error> {
+	let mut store = Store::open(launcher).await?;
+	let imported = import_into(launcher, &mut store, pack.as_ref(), id).await;
+	store.save().await?;
+
+	imported
+}
+
+async fn import_into(
+	launcher: &Launcher,
+	store: &mut Store,
+	pack: &Path,
+	id: Option<String>,
+) -> Result<Imported, Error> {
+	let mut archive = ZipArchive::new(File::open(pack).with_path(pack)?).with_path(pack)?;
+
+	let mut bytes = Vec::new();
+	archive
+		.by_name(INDEX)
+		.with_path(pack)?
+		.read_to_end(&mut bytes)
+		.with_path(pack)?;
+	let index: Index = serde_json::from_slice(&bytes).with_path(pack)?;
+
+	if index.format_version != 1 || index.game != "minecraft" {
+		return Err(Error::with_source(
+			ErrorKind::InvalidModpack,
+			format!(
+				"format version {} for {} is not supported",
+				index.format_version, index.game
+			),
+		))
+		.with_path(pack);
+	}
+	let (minecraft, loader) = components(&index.dependencies).with_path(pack)?;
+
+	let files = index
+		.files
+		.iter()
+		.filter(|file| {
+			file.env
+				.as_ref()
+				.is_none_or(|env| env.client != Support::Unsupported)
+		})
+		.map(|file| Ok((relative_path(&file.path)?, file)))
+		.collect::<Result<Vec<_>, Error>>()
+		.with_path(pack)?;
+
+	let id = id.unwrap_or_else(|| instance_id(&index.name));
+	let mut instance = Instance::new(launcher, id, minecraft).await?;
+
+	match populate(launcher, store, &mut archive, &mut instance, loader, &files).await {
+		Ok(overrides) => {
+			Ok(Imported {
+				instance,
+				name: index.name,
+				version: index.version_id,
+				files: files.len(),
+				overrides,
+			})
+		},
+		Err(err) => {
+			let _ = instance.remove(launcher, store).await;
+			Err(err).with_path(pack)
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn relative_paths() {
+		assert_eq!(
+			relative_path("mods/sodium.jar").unwrap(),
+			Path::new("mods/sodium.jar")
+		);
+		assert_eq!(
+			relative_path("config/a b.toml").unwrap(),
+			Path::new("config/a b.toml")
+		);
+
+		for path in [
+			"",
+			"/etc/passwd",
+			"../evil.jar",
+			"mods/../../evil.jar",
+			"./mods/a.jar",
+		] {
+			let err = relative_path(path).unwrap_err();
+			assert_eq!(err.kind(), ErrorKind::InvalidModpack, "{path}");
+		}
+	}
+
+	fn dependencies(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+		entries
+			.iter()
+			.map(|(id, version)| ((*id).to_owned(), (*version).to_owned()))
+			.collect()
+	}
+
+	#[test]
+	fn pack_components() {
+		let (minecraft, loader) = components(&dependencies(&[("minecraft", "1.20.1")])).unwrap();
+		assert_eq!(minecraft, Version::Id("1.20.1".to_owned()));
+		assert!(loader.is_none());
+
+		let (_, loader) = components(&dependencies(&[
+			("minecraft", "1.20.1"),
+			("fabric-loader", "0.14.21"),
+		]))
+		.unwrap();
+		assert!(matches!(loader, Some(ComponentEnum::FabricLoader(_))));
+
+		let (_, loader) = components(&dependencies(&[
+			("quilt-loader", "0.20.0"),
+			("minecraft", "1.20.1"),
+		]))
+		.unwrap();
+		assert!(matches!(loader, Some(ComponentEnum::QuiltLoader(_))));
+	}
+
+	#[test]
+	fn reject_unsupported_components() {
+		for entries in [
+			&[("fabric-loader", "0.14.21")][..],
+			&[("minecraft", "1.20.1"), ("forge", "47.1.0")],
+			&[
+				("minecraft", "1.20.1"),
+				("fabric-loader", "0.14.21"),
+				("quilt-loader", "0.20.0"),
+			],
+		] {
+			let err = components(&dependencies(entries)).unwrap_err();
+			assert_eq!(err.kind(), ErrorKind::InvalidModpack, "{entries:?}");
+		}
+	}
+
+	#[test]
+	fn instance_ids() {
+		assert_eq!(instance_id("Fabulously Optimized"), "Fabulously Optimized");
+		assert_eq!(instance_id("a/b\\c:d"), "a-b-c-d");
+		assert_eq!(instance_id("  ..hidden "), "hidden");
+		assert_eq!(instance_id("..."), "modpack");
+	}
+}
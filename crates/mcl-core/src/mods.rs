@@ -0,0 +1,396 @@
+//! Mods of an instance. Jars live in its `mods` folder, linked from the
+//! [`Store`], and are listed with their metadata in `mods.json` next to
+//! `meta.json`. Jars put into the folder by hand are picked up when the list
+//! is loaded.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use path_macro::path;
+use reqwest::IntoUrl;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::error::{Error, ErrorKind, ResultExt};
+use crate::store::{LinkMode, Store};
+use crate::utils::{self, sha512_file, Hashes};
+use crate::Launcher;
+
+pub mod metadata;
+
+pub use metadata::{ModLoader, ModMetadata};
+
+/// Appended to the file name of disabled mods, which loaders skip.
+const DISABLED_SUFFIX: &str = ".disabled";
+
+/// The Modrinth project and version a mod was installed from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ModrinthSource {
+	pub project_id: String,
+	pub version_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Mod {
+	/// File name in the mods folder, without [`DISABLED_SUFFIX`].
+	pub file: String,
+	pub enabled: bool,
+	#[serde(with = "hex")]
+	pub sha512: [u8; 64],
+	/// Where the jar was downloaded from.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub modrinth: Option<ModrinthSource>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub metadata: Option<ModMetadata>,
+}
+
+impl Mod {
+	/// Name of the file as it is in the mods folder.
+	#[must_use]
+	pub fn file_name(&self) -> String {
+		if self.enabled {
+			self.file.clone()
+		} else {
+			format!("{}{DISABLED_SUFFIX}", self.file)
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct ModList {
+	mods: Vec<Mod>,
+}
+
+/// Whether `file_name` looks like a mod, returning the name without
+/// [`DISABLED_SUFFIX`] and whether it is enabled.
+fn parse_file_name(file_name: &str) -> Option<(&str, bool)> {
+	let (file, enabled) = match file_name.strip_suffix(DISABLED_SUFFIX) {
+		Some(file) => (file, false),
+		None => (file_name, true),
+	};
+
+	(file.ends_with(".jar") || file.ends_with(".zip")).then_some((file, enabled))
+}
+
+/// Reads the metadata of the jar at `path` on the blocking pool, as the zip
+/// reader is synchronous. Jars without readable metadata have none.
+async fn read_metadata(path: &Path) -> Option<ModMetadata> {
+	let path = path.to_owned();
+	tokio::task::spawn_blocking(move || metadata::read(&path))
+		.await
+		.ok()
+		.and_then(Result::ok)
+		.flatten()
+}
+
+pub struct Mods {
+	instance_dir: PathBuf,
+	mods: Vec<Mod>,
+}
+
+impl Mods {
+	/// Loads the mod list of the instance at `instance_dir` and brings it in
+	/// line with its mods folder: jars added by hand are listed, missing ones
+	/// dropped and renamed ones enabled or disabled.
+	pub async fn load(instance_dir: impl AsRef<Path>) -> Result<Mods, Error> {
+		let instance_dir = instance_dir.as_ref().to_owned();
+		let path = path!(instance_dir / "mods.json");
+
+		let list: ModList = match fs::read(&path).await {
+			Ok(bytes) => serde_json::from_slice(&bytes).with_path(&path)?,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => ModList::default(),
+			Err(err) => return Err(err).with_path(&path),
+		};
+
+		let mut mods = Mods {
+			instance_dir,
+			mods: list.mods,
+		};
+		mods.scan().await?;
+
+		Ok(mods)
+	}
+
+	async fn scan(&mut self) -> Result<(), Error> {
+		let dir = self.dir();
+		let mut found = Vec::new();
+
+		match fs::read_dir(&dir).await {
+			Ok(mut entries) => {
+				while let Some(entry) = entries.next_entry().await.with_path(&dir)? {
+					let file_name = entry.file_name().to_string_lossy().into_owned();
+					if let Some((file, enabled)) = parse_file_name(&file_name) {
+						if entry.file_type().await.with_path(entry.path())?.is_file() {
+							found.push((file.to_owned(), enabled, entry.path()));
+						}
+					}
+				}
+			},
+			Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+			Err(err) => return Err(err).with_path(&dir),
+		}
+
+		self.mods
+			.retain(|listed| found.iter().any(|(file, ..)| *file == listed.file));
+
+		for (file, enabled, path) in found {
+			match self.mods.iter_mut().find(|listed| listed.file == file) {
+				Some(listed) => listed.enabled = enabled,
+				None => {
+					self.mods.push(Mod {
+						file,
+						enabled,
+						sha512: sha512_file(&path).await.with_path(&path)?,
+						url: None,
+						modrinth: None,
+						metadata: read_metadata(&path).await,
+					});
+				},
+			}
+		}
+
+		self.mods.sort_by(|a, b| a.file.cmp(&b.file));
+
+		Ok(())
+	}
+
+	/// Hashes every jar again, in case one was replaced under the same name.
+	/// Mods whose content changed lose their source.
+	pub async fn rehash(&mut self) -> Result<(), Error> {
+		let dir = self.dir();
+		for listed in &mut self.mods {
+			let path = path!(dir / listed.file_name());
+			let sha512 = sha512_file(&path).await.with_path(&path)?;
+			if sha512 != listed.sha512 {
+				listed.sha512 = sha512;
+				listed.url = None;
+				listed.modrinth = None;
+				listed.metadata = read_metadata(&path).await;
+			}
+		}
+
+		Ok(())
+	}
+
+	pub async fn save(&self) -> Result<(), Error> {
+		let path = path!(self.instance_dir / "mods.json");
+		let list = ModList {
+			mods: self.mods.clone(),
+		};
+		utils::write(&path, serde_json::to_string_pretty(&list)?)
+			.await
+			.with_path(&path)
+	}
+
+	/// The mods folder.
+	#[must_use]
+	pub fn dir(&self) -> PathBuf {
+		path!(self.instance_dir / "mods")
+	}
+
+	#[must_use]
+	pub fn list(&self) -> &[Mod] {
+		&self.mods
+	}
+
+	/// The mod with the file name `file`, with or without [`DISABLED_SUFFIX`].
+	#[must_use]
+	pub fn get(&self, file: &str) -> Option<&Mod> {
+		let file = file.strip_suffix(DISABLED_SUFFIX).unwrap_or(file);
+		self.mods.iter().find(|listed| listed.file == file)
+	}
+
+	#[must_use]
+	pub fn get_mut(&mut self, file: &str) -> Option<&mut Mod> {
+		let file = file.strip_suffix(DISABLED_SUFFIX).unwrap_or(file);
+		self.mods.iter_mut().find(|listed| listed.file == file)
+	}
+
+	/// The mod with the hex SHA-512 `sha512`.
+	#[must_use]
+	pub fn get_mut_by_sha512(&mut self, sha512: &str) -> Option<&mut Mod> {
+		self.mods
+			.iter_mut()
+			.find(|listed| hex::encode(listed.sha512) == sha512)
+	}
+
+	fn position(&self, file: &str) -> Result<usize, Error> {
+		let file = file.strip_suffix(DISABLED_SUFFIX).unwrap_or(file);
+		self.mods
+			.iter()
+			.position(|listed| listed.file == file)
+			.ok_or_else(|| Error::with_source(ErrorKind::ModNotFound, file.to_owned()))
+	}
+
+	/// Links a jar that is in the store into the mods folder as `file`.
+	pub async fn add_object(
+		&mut self,
+		store: &mut Store,
+		sha512: &[u8; 64],
+		file: &str,
+		url: Option<String>,
+	) -> Result<&Mod, Error> {
+		if file.contains(['/', '\\']) || parse_file_name(file).is_none_or(|(_, enabled)| !enabled) {
+			return Err(Error::with_source(ErrorKind::InvalidMod, file.to_owned()));
+		}
+		if self.get(file).is_some() {
+			return Err(Error::with_source(
+				ErrorKind::ModAlreadyExists,
+				file.to_owned(),
+			));
+		}
+
+		let path = path!(self.dir() / file);
+		store.link(sha512, &path, LinkMode::Shared).await?;
+
+		self.mods.push(Mod {
+			file: file.to_owned(),
+			enabled: true,
+			sha512: *sha512,
+			url,
+			modrinth: None,
+			metadata: read_metadata(&path).await,
+		});
+		self.mods.sort_by(|a, b| a.file.cmp(&b.file));
+
+		self.get(file)
+			.ok_or_else(|| Error::with_source(ErrorKind::ModNotFound, file.to_owned()))
+	}
+
+	/// Copies the jar at `path` into the store and adds it.
+	pub async fn add_file(
+		&mut self,
+		store: &mut Store,
+		path: impl AsRef<Path>,
+	) -> Result<&Mod, Error> {
+		let path = path.as_ref();
+		let file = path
+			.file_name()
+			.map(|file| file.to_string_lossy().into_owned())
+			.unwrap_or_default();
+
+		if self.get(&file).is_some() {
+			return Err(Error::with_source(ErrorKind::ModAlreadyExists, file));
+		}
+		let sha512 = store.insert_file(path).await?;
+
+		self.add_object(store, &sha512, &file, None).await
+	}
+
+	/// Downloads the jar at `url` into the store, verified against `hashes`,
+	/// and adds it under the last segment of the URL.
+	pub async fn add_url(
+		&mut self,
+		launcher: &Launcher,
+		store: &mut Store,
+		url: impl IntoUrl,
+		hashes: impl Into<Hashes>,
+	) -> Result<&Mod, Error> {
+		let url = url.into_url()?;
+		let file = url
+			.path_segments()
+			.and_then(Iterator::last)
+			.map(percent_decode)
+			.unwrap_or_default();
+
+		if self.get(&file).is_some() {
+			return Err(Error::with_source(ErrorKind::ModAlreadyExists, file));
+		}
+		let sha512 = store
+			.download(
+				launcher.http(),
+				launcher.settings().mirror(url.as_str()),
+				hashes,
+			)
+			.await?;
+
+		self.add_object(store, &sha512, &file, Some(url.to_string()))
+			.await
+	}
+
+	/// Renames the jar to or from its `.disabled` name.
+	pub async fn set_enabled(
+		&mut self,
+		store: &mut Store,
+		file: &str,
+		enabled: bool,
+	) -> Result<(), Error> {
+		let index = self.position(file)?;
+		let dir = self.dir();
+		let listed = &mut self.mods[index];
+		if listed.enabled == enabled {
+			return Ok(());
+		}
+
+		let from = path!(dir / listed.file_name());
+		listed.enabled = enabled;
+		let to = path!(dir / listed.file_name());
+
+		fs::rename(&from, &to).await.with_path(&from)?;
+		store.move_references(&from, &to);
+
+		Ok(())
+	}
+
+	/// Deletes the jar and removes it from the list.
+	pub async fn remove(&mut self, store: &mut Store, file: &str) -> Result<Mod, Error> {
+		let index = self.position(file)?;
+		let path = path!(self.dir() / self.mods[index].file_name());
+
+		match fs::remove_file(&path).await {
+			Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err).with_path(&path),
+			_ => {},
+		}
+		store.forget(&path);
+
+		Ok(self.mods.remove(index))
+	}
+}
+
+/// Decodes `%XX` escapes in a URL path segment, like the `%2B` of a `+` in
+/// file names.
+fn percent_decode(segment: &str) -> String {
+	let bytes = segment.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+
+	while i < bytes.len() {
+		let escaped = (bytes[i] == b'%')
+			.then(|| segment.get(i + 1..i + 3))
+			.flatten()
+			.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+		match escaped {
+			Some(byte) => {
+				decoded.push(byte);
+				i += 3;
+			},
+			None => {
+				decoded.push(bytes[i]);
+				i += 1;
+			},
+		}
+	}
+
+	String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn percent_decode_segments() {
+		assert_eq!(percent_decode("sodium-0.5.3.jar"), "sodium-0.5.3.jar");
+		assert_eq!(
+			percent_decode("fabric-api-0.90.0%2B1.20.1.jar"),
+			"fabric-api-0.90.0+1.20.1.jar"
+		);
+		assert_eq!(percent_decode("caf%C3%A9%20mod.jar"), "café mod.jar");
+		// Invalid escapes are kept as they are
+		assert_eq!(percent_decode("100%.jar"), "100%.jar");
+		assert_eq!(percent_decode("%zz%4"), "%zz%4");
+	}
+}
@@ -0,0 +1,408 @@
+//! What a mod jar says about itself, read from the metadata file of the loader
+//! it was made for.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::error::{Error, ResultExt};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModLoader {
+	Fabric,
+	Quilt,
+	/// Forge since 1.13, with `META-INF/mods.toml`.
+	Forge,
+	/// Forge before 1.13, with `mcmod.info`.
+	LegacyForge,
+}
+
+impl fmt::Display for ModLoader {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			ModLoader::Fabric => "fabric",
+			ModLoader::Quilt => "quilt",
+			ModLoader::Forge => "forge",
+			ModLoader::LegacyForge => "legacy-forge",
+		})
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ModMetadata {
+	pub loader: ModLoader,
+	pub id: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub version: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub authors: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FabricPerson {
+	Name(String),
+	Object { name: String },
+}
+
+#[derive(Deserialize)]
+struct FabricMod {
+	id: String,
+	name: Option<String>,
+	version: Option<String>,
+	description: Option<String>,
+	#[serde(default)]
+	authors: Vec<FabricPerson>,
+}
+
+#[derive(Deserialize, Default)]
+struct QuiltMetadata {
+	name: Option<String>,
+	description: Option<String>,
+	/// Names mapped to their roles.
+	#[serde(default)]
+	contributors: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct QuiltLoader {
+	id: String,
+	version: Option<String>,
+	#[serde(default)]
+	metadata: QuiltMetadata,
+}
+
+#[derive(Deserialize)]
+struct QuiltMod {
+	quilt_loader: QuiltLoader,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Authors {
+	/// Usually a comma separated list.
+	One(String),
+	Many(Vec<String>),
+}
+
+impl Authors {
+	fn into_vec(self) -> Vec<String> {
+		match self {
+			Authors::One(authors) => {
+				authors
+					.split(',')
+					.map(|author| author.trim().to_owned())
+					.filter(|author| !author.is_empty())
+					.collect()
+			},
+			Authors::Many(authors) => authors,
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ForgeMod {
+	mod_id: String,
+	version: Option<String>,
+	display_name: Option<String>,
+	description: Option<String>,
+	authors: Option<Authors>,
+}
+
+#[derive(Deserialize)]
+struct ModsToml {
+	mods: Vec<ForgeMod>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LegacyForgeMod {
+	modid: String,
+	name: Option<String>,
+	version: Option<String>,
+	description: Option<String>,
+	#[serde(default, alias = "authors")]
+	author_list: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum McmodInfo {
+	List(Vec<LegacyForgeMod>),
+	#[serde(rename_all = "camelCase")]
+	Versioned {
+		mod_list: Vec<LegacyForgeMod>,
+	},
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<Vec<u8>>, Error> {
+	match archive.by_name(name) {
+		Ok(mut file) => {
+			let mut bytes = Vec::new();
+			file.read_to_end(&mut bytes)?;
+			Ok(Some(bytes))
+		},
+		Err(ZipError::FileNotFound) => Ok(None),
+		Err(err) => Err(err.into()),
+	}
+}
+
+/// Many mods have line breaks inside strings of their JSON metadata, which
+/// loaders accept.
+fn parse_json<T: DeserializeOwned>(bytes: &[u8]) -> Option<T> {
+	serde_json::from_slice(bytes).ok().or_else(|| {
+		serde_json::from_str(&String::from_utf8_lossy(bytes).replace(['\n', '\r', '\t'], " ")).ok()
+	})
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+	value.filter(|value| !value.trim().is_empty())
+}
+
+/// `Implementation-Version` from the manifest, which `${file.jarVersion}` in
+/// `mods.toml` refers to.
+fn jar_version(archive: &mut ZipArchive<File>) -> Result<Option<String>, Error> {
+	let Some(manifest) = read_entry(archive, "META-INF/MANIFEST.MF")? else {
+		return Ok(None);
+	};
+
+	Ok(String::from_utf8_lossy(&manifest).lines().find_map(|line| {
+		line.strip_prefix("Implementation-Version:")
+			.map(|version| version.trim().to_owned())
+	}))
+}
+
+/// Reads the metadata of the first mod in `jar`. `None` if the jar has no
+/// metadata of a supported loader or it can not be parsed.
+pub fn read(jar: &Path) -> Result<Option<ModMetadata>, Error> {
+	let mut archive = ZipArchive::new(File::open(jar).with_path(jar)?).with_path(jar)?;
+
+	// Quilt mods often carry a `fabric.mod.json` too
+	if let Some(bytes) = read_entry(&mut archive, "quilt.mod.json").with_path(jar)? {
+		if let Some(QuiltMod { quilt_loader }) = parse_json::<QuiltMod>(&bytes) {
+			return Ok(Some(ModMetadata {
+				loader: ModLoader::Quilt,
+				id: quilt_loader.id,
+				name: non_empty(quilt_loader.metadata.name),
+				version: non_empty(quilt_loader.version),
+				description: non_empty(quilt_loader.metadata.description),
+				authors: quilt_loader.metadata.contributors.into_keys().collect(),
+			}));
+		}
+	}
+
+	if let Some(bytes) = read_entry(&mut archive, "fabric.mod.json").with_path(jar)? {
+		if let Some(fabric_mod) = parse_json::<FabricMod>(&bytes) {
+			return Ok(Some(ModMetadata {
+				loader: ModLoader::Fabric,
+				id: fabric_mod.id,
+				name: non_empty(fabric_mod.name),
+				version: non_empty(fabric_mod.version),
+				description: non_empty(fabric_mod.description),
+				authors: fabric_mod
+					.authors
+					.into_iter()
+					.map(|person| {
+						match person {
+							FabricPerson::Name(name) | FabricPerson::Object { name } => name,
+						}
+					})
+					.collect(),
+			}));
+		}
+	}
+
+	if let Some(bytes) = read_entry(&mut archive, "META-INF/mods.toml").with_path(jar)? {
+		let mods_toml = std::str::from_utf8(&bytes)
+			.ok()
+			.and_then(|text| toml::from_str::<ModsToml>(text).ok());
+		if let Some(forge_mod) = mods_toml.and_then(|mods_toml| mods_toml.mods.into_iter().next()) {
+			let version = match forge_mod.version {
+				Some(version) if version.contains("${file.jarVersion}") => {
+					jar_version(&mut archive)
+						.with_path(jar)?
+						.map(|jar_version| version.replace("${file.jarVersion}", &jar_version))
+				},
+				version => version,
+			};
+
+			return Ok(Some(ModMetadata {
+				loader: ModLoader::Forge,
+				id: forge_mod.mod_id,
+				name: non_empty(forge_mod.display_name),
+				version: non_empty(version),
+				description: non_empty(forge_mod.description.map(|text| text.trim().to_owned())),
+				authors: forge_mod.authors.map(Authors::into_vec).unwrap_or_default(),
+			}));
+		}
+	}
+
+	if let Some(bytes) = read_entry(&mut archive, "mcmod.info").with_path(jar)? {
+		let mods = match parse_json::<McmodInfo>(&bytes) {
+			Some(McmodInfo::List(mods) | McmodInfo::Versioned { mod_list: mods }) => mods,
+			None => Vec::new(),
+		};
+		if let Some(legacy_mod) = mods.into_iter().next() {
+			return Ok(Some(ModMetadata {
+				loader: ModLoader::LegacyForge,
+				id: legacy_mod.modid,
+				name: non_empty(legacy_mod.name),
+				version: non_empty(legacy_mod.version),
+				description: non_empty(legacy_mod.description),
+				authors: legacy_mod.author_list,
+			}));
+		}
+	}
+
+	Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+	use std::path::PathBuf;
+
+	use zip::write::FileOptions;
+	use zip::{CompressionMethod, ZipWriter};
+
+	use super::*;
+
+	/// Writes a jar with `entries` to the temporary directory.
+	fn jar(name: &str, entries: &[(&str, &str)]) -> PathBuf {
+		let path =
+			std::env::temp_dir().join(format!("mcl-metadata-{}-{name}.jar", std::process::id()));
+		let mut writer = ZipWriter::new(File::create(&path).unwrap());
+		for (name, contents) in entries {
+			writer
+				.start_file(
+					*name,
+					FileOptions::default().compression_method(CompressionMethod::Stored),
+				)
+				.unwrap();
+			writer.write_all(contents.as_bytes()).unwrap();
+		}
+		writer.finish().unwrap();
+		path
+	}
+
+	fn read_jar(name: &str, entries: &[(&str, &str)]) -> Option<ModMetadata> {
+		let path = jar(name, entries);
+		let metadata = read(&path).unwrap();
+		std::fs::remove_file(path).unwrap();
+		metadata
+	}
+
+	#[test]
+	fn read_fabric() {
+		let metadata = read_jar("fabric", &[(
+			"fabric.mod.json",
+			r#"{
+				"id": "sodium",
+				"name": "Sodium",
+				"version": "0.5.3",
+				"description": "A rendering engine
+					with a line break",
+				"authors": ["JellySquid", { "name": "IMS" }]
+			}"#,
+		)]);
+
+		let metadata = metadata.unwrap();
+		assert_eq!(metadata.loader, ModLoader::Fabric);
+		assert_eq!(metadata.id, "sodium");
+		assert_eq!(metadata.name.as_deref(), Some("Sodium"));
+		assert_eq!(metadata.version.as_deref(), Some("0.5.3"));
+		assert!(metadata
+			.description
+			.unwrap()
+			.starts_with("A rendering engine"));
+		assert_eq!(metadata.authors, ["JellySquid", "IMS"]);
+	}
+
+	#[test]
+	fn read_quilt_before_fabric() {
+		let metadata = read_jar("quilt", &[
+			("fabric.mod.json", r#"{ "id": "fabric-id" }"#),
+			(
+				"quilt.mod.json",
+				r#"{
+					"quilt_loader": {
+						"id": "quilt-id",
+						"version": "1.0.0",
+						"metadata": { "name": "", "contributors": { "Someone": "Owner" } }
+					}
+				}"#,
+			),
+		]);
+
+		assert_eq!(
+			metadata,
+			Some(ModMetadata {
+				loader: ModLoader::Quilt,
+				id: "quilt-id".to_owned(),
+				name: None,
+				version: Some("1.0.0".to_owned()),
+				description: None,
+				authors: vec!["Someone".to_owned()],
+			})
+		);
+	}
+
+	#[test]
+	fn read_forge_with_jar_version() {
+		let metadata = read_jar("forge", &[
+			(
+				"META-INF/mods.toml",
+				r#"
+					modLoader = "javafml"
+					[[mods]]
+					modId = "jei"
+					version = "${file.jarVersion}"
+					displayName = "Just Enough Items"
+					authors = "mezz, Someone Else"
+				"#,
+			),
+			(
+				"META-INF/MANIFEST.MF",
+				"Manifest-Version: 1.0\r\nImplementation-Version: 15.2.0.27\r\n",
+			),
+		]);
+
+		let metadata = metadata.unwrap();
+		assert_eq!(metadata.loader, ModLoader::Forge);
+		assert_eq!(metadata.id, "jei");
+		assert_eq!(metadata.version.as_deref(), Some("15.2.0.27"));
+		assert_eq!(metadata.authors, ["mezz", "Someone Else"]);
+	}
+
+	#[test]
+	fn read_legacy_forge() {
+		let metadata = read_jar("legacy-forge", &[(
+			"mcmod.info",
+			r#"{ "modListVersion": 2, "modList": [{ "modid": "old", "authors": ["A"] }] }"#,
+		)]);
+
+		let metadata = metadata.unwrap();
+		assert_eq!(metadata.loader, ModLoader::LegacyForge);
+		assert_eq!(metadata.id, "old");
+		assert_eq!(metadata.authors, ["A"]);
+	}
+
+	#[test]
+	fn read_without_metadata() {
+		assert_eq!(read_jar("none", &[("a.class", "")]), None);
+		assert_eq!(read_jar("broken", &[("fabric.mod.json", "{")]), None);
+	}
+}
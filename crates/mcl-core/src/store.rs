@@ -0,0 +1,723 @@
+//! Content addressed storage shared by all instances. Files like mods and
+//! resource packs are kept once in `store/objects` under their SHA-512 and
+//! linked into every instance that uses them.
+//!
+//! The store counts references to each object in `store/index.json`. Garbage
+//! collection drops references to files that were deleted or changed and
+//! removes objects nothing references anymore. Removing an object never
+//! loses instance data: hardlinks keep the content alive and reflinks and
+//! copies are independent of it.
+//!
+//! An open [`Store`] holds an advisory lock on `store/lock`, so launcher
+//! processes sharing the data directory take turns with the index.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use path_macro::path;
+use reqwest::IntoUrl;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::error::{Error, ErrorKind, ResultExt};
+use crate::utils::{self, sha512_file, Hash, Hashes};
+use crate::Launcher;
+
+/// Distinguishes temporary files of concurrent inserts in one process.
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// How old a temporary file has to be before garbage collection removes it.
+/// Younger ones may belong to an insert still in progress.
+const TEMP_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How an object may be placed in an instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkMode {
+	/// Reflink, else hardlink, else copy. For files that are replaced rather
+	/// than edited, like mods and resource packs, as editing a hardlinked file
+	/// changes the object for every instance.
+	Shared,
+	/// Reflink, else copy. For files the game edits in place, like configs.
+	Private,
+}
+
+/// How an object ended up in an instance.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Linked {
+	Reflink,
+	Hardlink,
+	Copy,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct DedupeReport {
+	/// Files now linked from the store.
+	pub files: usize,
+	/// Size of the files whose content was already in the store.
+	pub saved_bytes: u64,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct GcReport {
+	/// References to files that were deleted or no longer match their object.
+	pub dropped_references: usize,
+	pub removed_objects: usize,
+	/// Size of the removed objects no instance file was hardlinked to.
+	pub freed_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Index {
+	/// Files linked from each object, keyed by the hex SHA-512 of the object.
+	/// Paths inside the data directory are relative to it.
+	references: BTreeMap<String, BTreeSet<PathBuf>>,
+}
+
+pub struct Store {
+	data_dir: PathBuf,
+	index: Index,
+	/// Unlocked when the store is dropped.
+	_lock: std::fs::File,
+}
+
+#[cfg(unix)]
+fn same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+	use std::os::unix::fs::MetadataExt;
+
+	a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_: &std::fs::Metadata, _: &std::fs::Metadata) -> bool {
+	false
+}
+
+/// Whether a hardlink other than this one keeps the content alive.
+#[cfg(unix)]
+fn has_other_links(metadata: &std::fs::Metadata) -> bool {
+	use std::os::unix::fs::MetadataExt;
+
+	metadata.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn has_other_links(_: &std::fs::Metadata) -> bool {
+	false
+}
+
+/// Waits for an exclusive lock on `file`, released when it is closed.
+#[cfg(unix)]
+fn lock_exclusive(file: &std::fs::File) -> io::Result<()> {
+	rustix::fs::flock(file, rustix::fs::FlockOperation::LockExclusive).map_err(io::Error::from)
+}
+
+#[cfg(not(unix))]
+fn lock_exclusive(_: &std::fs::File) -> io::Result<()> {
+	Ok(())
+}
+
+async fn lock(path: PathBuf) -> Result<std::fs::File, Error> {
+	tokio::task::spawn_blocking(move || {
+		let file = std::fs::OpenOptions::new()
+			.create(true)
+			.truncate(false)
+			.write(true)
+			.open(&path)
+			.with_path(&path)?;
+		lock_exclusive(&file).with_path(&path)?;
+		Ok(file)
+	})
+	.await
+	.map_err(io::Error::from)?
+}
+
+async fn reflink(from: &Path, to: &Path) -> io::Result<()> {
+	let (from, to) = (from.to_owned(), to.to_owned());
+	tokio::task::spawn_blocking(move || reflink_copy::reflink(from, to)).await?
+}
+
+/// Puts a link to `object` in place of `dest`, trying the ways `allowed` in
+/// order. The link is created next to `dest` first, so a failure leaves
+/// `dest` untouched.
+async fn replace_with_link(
+	object: &Path,
+	dest: &Path,
+	allowed: &[Linked],
+) -> io::Result<Option<Linked>> {
+	if let Some(dir) = dest.parent() {
+		fs::create_dir_all(dir).await?;
+	}
+
+	let mut temp = dest.as_os_str().to_owned();
+	temp.push(".link");
+	let temp = PathBuf::from(temp);
+	let _ = fs::remove_file(&temp).await;
+
+	for &linked in allowed {
+		let result = match linked {
+			Linked::Reflink => reflink(object, &temp).await,
+			Linked::Hardlink => fs::hard_link(object, &temp).await,
+			Linked::Copy => fs::copy(object, &temp).await.map(|_| ()),
+		};
+
+		if result.is_ok() {
+			return match fs::rename(&temp, dest).await {
+				Ok(()) => Ok(Some(linked)),
+				Err(err) => {
+					let _ = fs::remove_file(&temp).await;
+					Err(err)
+				},
+			};
+		}
+		let _ = fs::remove_file(&temp).await;
+	}
+
+	Ok(None)
+}
+
+impl Store {
+	/// Loads the store of the launcher's data directory, waiting until no
+	/// other process has it open. Opening it twice in one process blocks
+	/// forever, so pass the open store along instead.
+	pub async fn open(launcher: &Launcher) -> Result<Store, Error> {
+		let data_dir = launcher.data_dir().to_owned();
+		let dir = path!(data_dir / "store");
+		fs::create_dir_all(&dir).await.with_path(&dir)?;
+		let lock = lock(path!(dir / "lock")).await?;
+
+		let path = path!(dir / "index.json");
+
+		let index = match fs::read(&path).await {
+			Ok(bytes) => serde_json::from_slice(&bytes).with_path(&path)?,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Index::default(),
+			Err(err) => return Err(err).with_path(&path),
+		};
+
+		Ok(Store {
+			data_dir,
+			index,
+			_lock: lock,
+		})
+	}
+
+	pub async fn save(&self) -> Result<(), Error> {
+		let path = path!(self.dir() / "index.json");
+		utils::write(&path, serde_json::to_string_pretty(&self.index)?)
+			.await
+			.with_path(&path)
+	}
+
+	#[must_use]
+	pub fn dir(&self) -> PathBuf {
+		path!(self.data_dir / "store")
+	}
+
+	#[must_use]
+	pub fn object_path(&self, sha512: &[u8; 64]) -> PathBuf {
+		let hex = hex::encode(sha512);
+		path!(self.dir() / "objects" / &hex[..2] / hex)
+	}
+
+	/// How many files link to the object.
+	#[must_use]
+	pub fn references(&self, sha512: &[u8; 64]) -> usize {
+		self.index
+			.references
+			.get(&hex::encode(sha512))
+			.map_or(0, BTreeSet::len)
+	}
+
+	fn temp_path(&self) -> PathBuf {
+		let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+		path!(self.dir() / "tmp" / format!("{}-{counter}", std::process::id()))
+	}
+
+	/// The path of the object if it is there with the content of its hash. An
+	/// instance file hardlinked to the object may have been edited in place.
+	async fn intact_object(&self, sha512: &[u8; 64]) -> Option<PathBuf> {
+		let object = self.object_path(sha512);
+		sha512_file(&object)
+			.await
+			.is_ok_and(|hash| hash == *sha512)
+			.then_some(object)
+	}
+
+	/// Hashes the file at `temp` and moves it into the store, replacing an
+	/// object that was changed. Files hardlinked to that keep their content.
+	async fn insert_temp(&self, temp: &Path) -> Result<[u8; 64], Error> {
+		let sha512 = sha512_file(temp).await.with_path(temp)?;
+		let object = self.object_path(&sha512);
+
+		if self.intact_object(&sha512).await.is_some() {
+			fs::remove_file(temp).await.with_path(temp)?;
+		} else {
+			if let Some(dir) = object.parent() {
+				fs::create_dir_all(dir).await.with_path(dir)?;
+			}
+			fs::rename(temp, &object).await.with_path(&object)?;
+		}
+
+		Ok(sha512)
+	}
+
+	/// Copies the file at `path` into the store, returning its SHA-512.
+	pub async fn insert_file(&self, path: impl AsRef<Path>) -> Result<[u8; 64], Error> {
+		let path = path.as_ref();
+		let temp = self.temp_path();
+		if let Some(dir) = temp.parent() {
+			fs::create_dir_all(dir).await.with_path(dir)?;
+		}
+
+		let (from, to) = (path.to_owned(), temp.clone());
+		tokio::task::spawn_blocking(move || reflink_copy::reflink_or_copy(from, to))
+			.await
+			.map_err(io::Error::from)
+			.and_then(|result| result)
+			.with_path(path)?;
+
+		self.insert_temp(&temp).await
+	}
+
+	/// Downloads `url` into the store unless an intact object with its SHA-512
+	/// is there already, verifying it against `hashes`.
+	pub async fn download(
+		&self,
+		http: &reqwest::Client,
+		url: impl IntoUrl,
+		hashes: impl Into<Hashes>,
+	) -> Result<[u8; 64], Error> {
+		let hashes = hashes.into();
+		if let Some(Hash::Sha512(sha512)) = hashes.get("sha512") {
+			if self.intact_object(&sha512).await.is_some() {
+				return Ok(sha512);
+			}
+		}
+
+		let temp = self.temp_path();
+		utils::download_file(http, url, &temp, hashes, false).await?;
+		self.insert_temp(&temp).await
+	}
+
+	/// How `path` is recorded in the index.
+	fn reference(&self, path: &Path) -> PathBuf {
+		path.strip_prefix(&self.data_dir).unwrap_or(path).to_owned()
+	}
+
+	/// Places the object at `dest`, replacing what is there, and records the
+	/// reference. Call [`Store::save`] afterwards to keep it.
+	pub async fn link(
+		&mut self,
+		sha512: &[u8; 64],
+		dest: impl AsRef<Path>,
+		mode: LinkMode,
+	) -> Result<Linked, Error> {
+		let dest = dest.as_ref();
+		let Some(object) = self.intact_object(sha512).await else {
+			return Err(Error::with_source(
+				ErrorKind::ArtifactNotFound,
+				format!("no intact object {} in the store", hex::encode(sha512)),
+			))
+			.with_path(dest);
+		};
+
+		let allowed: &[Linked] = match mode {
+			LinkMode::Shared => &[Linked::Reflink, Linked::Hardlink, Linked::Copy],
+			LinkMode::Private => &[Linked::Reflink, Linked::Copy],
+		};
+		let linked = replace_with_link(&object, dest, allowed)
+			.await
+			.with_path(dest)?
+			.ok_or_else(|| Error::with_source(ErrorKind::Io, "could not link from the store"))
+			.with_path(dest)?;
+
+		self.add_reference(sha512, dest);
+
+		Ok(linked)
+	}
+
+	/// Records that `path` links to the object, and nothing else.
+	fn add_reference(&mut self, sha512: &[u8; 64], path: &Path) {
+		self.forget(path);
+		let reference = self.reference(path);
+		self.index
+			.references
+			.entry(hex::encode(sha512))
+			.or_default()
+			.insert(reference);
+	}
+
+	/// Drops the references of `path` or files below it, like when an instance
+	/// is removed. The files themselves are left alone. Returns whether any
+	/// were dropped.
+	pub fn forget(&mut self, path: impl AsRef<Path>) -> bool {
+		let prefix = self.reference(path.as_ref());
+		let mut forgotten = false;
+		self.index.references.retain(|_, references| {
+			references.retain(|reference| {
+				let below = reference.starts_with(&prefix);
+				forgotten |= below;
+				!below
+			});
+			!references.is_empty()
+		});
+
+		forgotten
+	}
+
+	/// Updates references to `from` or files below it after it was moved to
+	/// `to`, like when an instance is renamed. Returns whether any were.
+	pub fn move_references(&mut self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> bool {
+		let (from, to) = (self.reference(from.as_ref()), self.reference(to.as_ref()));
+		let mut moved = false;
+
+		for references in self.index.references.values_mut() {
+			*references = std::mem::take(references)
+				.into_iter()
+				.map(|reference| {
+					match reference.strip_prefix(&from) {
+						Ok(rest) => {
+							moved = true;
+							if rest.as_os_str().is_empty() {
+								to.clone()
+							} else {
+								path!(to / rest)
+							}
+						},
+						Err(_) => reference,
+					}
+				})
+				.collect();
+		}
+
+		moved
+	}
+
+	/// Moves every file below `dir` into the store and links it back. Files
+	/// that can not be reflinked, or hardlinked in [`LinkMode::Shared`], are
+	/// left alone and get no reference.
+	pub async fn dedupe(
+		&mut self,
+		dir: impl AsRef<Path>,
+		mode: LinkMode,
+	) -> Result<DedupeReport, Error> {
+		let mut report = DedupeReport::default();
+		let mut dirs = vec![dir.as_ref().to_owned()];
+
+		while let Some(dir) = dirs.pop() {
+			let mut entries = match fs::read_dir(&dir).await {
+				Ok(entries) => entries,
+				Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+				Err(err) => return Err(err).with_path(&dir),
+			};
+
+			while let Some(entry) = entries.next_entry().await.with_path(&dir)? {
+				let path = entry.path();
+				let file_type = entry.file_type().await.with_path(&path)?;
+				if file_type.is_dir() {
+					dirs.push(path);
+					continue;
+				}
+				if !file_type.is_file() {
+					continue;
+				}
+
+				let metadata = entry.metadata().await.with_path(&path)?;
+				let sha512 = sha512_file(&path).await.with_path(&path)?;
+
+				// A copy would take as much space as the file it replaces
+				let allowed: &[Linked] = match mode {
+					LinkMode::Shared => &[Linked::Reflink, Linked::Hardlink],
+					LinkMode::Private => &[Linked::Reflink],
+				};
+				let existing = fs::metadata(self.object_path(&sha512)).await;
+				if existing.is_ok_and(|existing| same_file(&existing, &metadata)) {
+					// Linked already
+				} else if let Some(object) = self.intact_object(&sha512).await {
+					if replace_with_link(&object, &path, allowed)
+						.await
+						.with_path(&path)?
+						.is_none()
+					{
+						continue;
+					}
+					report.saved_bytes += metadata.len();
+				} else {
+					// The instance file becomes the object
+					let temp = self.temp_path();
+					if replace_with_link(&path, &temp, allowed)
+						.await
+						.with_path(&path)?
+						.is_none()
+					{
+						continue;
+					}
+					self.insert_temp(&temp).await?;
+				}
+
+				self.add_reference(&sha512, &path);
+				report.files += 1;
+			}
+		}
+
+		Ok(report)
+	}
+
+	/// Whether the file at `reference` still has the content of `object`.
+	async fn still_linked(&self, object: &Path, sha512: &[u8; 64], reference: &Path) -> bool {
+		let path = path!(self.data_dir / reference);
+		let (Ok(metadata), Ok(object)) = (fs::metadata(&path).await, fs::metadata(object).await)
+		else {
+			return false;
+		};
+
+		if same_file(&metadata, &object) {
+			return true;
+		}
+		metadata.is_file()
+			&& metadata.len() == object.len()
+			&& sha512_file(&path).await.is_ok_and(|hash| hash == *sha512)
+	}
+
+	/// Drops references to files that are gone or were changed, then removes
+	/// objects that were changed or have no references and temporary files
+	/// older than a day. Saves the index.
+	pub async fn collect_garbage(&mut self) -> Result<GcReport, Error> {
+		let mut report = GcReport::default();
+
+		let index = std::mem::take(&mut self.index.references);
+		for (hex, references) in index {
+			let Some(sha512) = hex::decode(&hex)
+				.ok()
+				.and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+			else {
+				report.dropped_references += references.len();
+				continue;
+			};
+			let object = self.object_path(&sha512);
+
+			// Editing a hardlinked file in place changes the object, which then
+			// has to leave the store
+			if !sha512_file(&object).await.is_ok_and(|hash| hash == sha512) {
+				report.dropped_references += references.len();
+				continue;
+			}
+
+			let mut kept = BTreeSet::new();
+			for reference in references {
+				if self.still_linked(&object, &sha512, &reference).await {
+					kept.insert(reference);
+				} else {
+					report.dropped_references += 1;
+				}
+			}
+
+			if !kept.is_empty() {
+				self.index.references.insert(hex, kept);
+			}
+		}
+
+		let objects_dir = path!(self.dir() / "objects");
+		if let Ok(mut prefixes) = fs::read_dir(&objects_dir).await {
+			while let Some(prefix) = prefixes.next_entry().await.with_path(&objects_dir)? {
+				let prefix = prefix.path();
+				let mut objects = fs::read_dir(&prefix).await.with_path(&prefix)?;
+
+				while let Some(object) = objects.next_entry().await.with_path(&prefix)? {
+					let referenced = object
+						.file_name()
+						.to_str()
+						.is_some_and(|hex| self.index.references.contains_key(hex));
+					if referenced {
+						continue;
+					}
+
+					let path = object.path();
+					let metadata = object.metadata().await.with_path(&path)?;
+					fs::remove_file(&path).await.with_path(&path)?;
+					report.removed_objects += 1;
+					if !has_other_links(&metadata) {
+						report.freed_bytes += metadata.len();
+					}
+				}
+
+				// Fails unless the prefix directory is empty now
+				let _ = fs::remove_dir(&prefix).await;
+			}
+		}
+
+		let temp_dir = path!(self.dir() / "tmp");
+		if let Ok(mut temps) = fs::read_dir(&temp_dir).await {
+			while let Some(temp) = temps.next_entry().await.with_path(&temp_dir)? {
+				let path = temp.path();
+				let metadata = temp.metadata().await.with_path(&path)?;
+				let stale = metadata
+					.modified()
+					.ok()
+					.and_then(|modified| modified.elapsed().ok())
+					.is_some_and(|age| age > TEMP_MAX_AGE);
+				if metadata.is_file() && stale {
+					fs::remove_file(&path).await.with_path(&path)?;
+				}
+			}
+		}
+
+		self.save().await?;
+
+		Ok(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn test_store_dir(name: &str) -> PathBuf {
+		std::env::temp_dir().join(format!("mcl-store-{name}-{}", std::process::id()))
+	}
+
+	#[tokio::test]
+	async fn link_objects() {
+		let data_dir = test_store_dir("link");
+		let launcher = Launcher::new(&data_dir);
+		let mut store = Store::open(&launcher).await.unwrap();
+
+		let source = path!(data_dir / "source.jar");
+		fs::write(&source, b"mod").await.unwrap();
+		let sha512 = store.insert_file(&source).await.unwrap();
+		assert_eq!(sha512, openssl::sha::sha512(b"mod"));
+
+		let shared = path!(data_dir / "instances" / "a" / "mods" / "mod.jar");
+		let private = path!(data_dir / "instances" / "b" / "config" / "mod.toml");
+		store
+			.link(&sha512, &shared, LinkMode::Shared)
+			.await
+			.unwrap();
+		let linked = store
+			.link(&sha512, &private, LinkMode::Private)
+			.await
+			.unwrap();
+		assert_ne!(linked, Linked::Hardlink);
+		assert_eq!(fs::read(&shared).await.unwrap(), b"mod");
+		assert_eq!(fs::read(&private).await.unwrap(), b"mod");
+		assert_eq!(store.references(&sha512), 2);
+
+		assert!(store.forget(path!(data_dir / "instances" / "a")));
+		assert_eq!(store.references(&sha512), 1);
+
+		// A changed object is not linked anywhere else
+		fs::write(store.object_path(&sha512), b"edited")
+			.await
+			.unwrap();
+		let err = store
+			.link(&sha512, path!(data_dir / "c.jar"), LinkMode::Shared)
+			.await
+			.unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::ArtifactNotFound);
+		assert_eq!(fs::read(&private).await.unwrap(), b"mod");
+
+		drop(store);
+		fs::remove_dir_all(&data_dir).await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn dedupe_files() {
+		let data_dir = test_store_dir("dedupe");
+		let launcher = Launcher::new(&data_dir);
+		let mut store = Store::open(&launcher).await.unwrap();
+
+		let mods = path!(data_dir / "instances" / "a" / "mods");
+		fs::create_dir_all(path!(mods / "nested")).await.unwrap();
+		fs::write(path!(mods / "one.jar"), b"same").await.unwrap();
+		fs::write(path!(mods / "nested" / "two.jar"), b"same")
+			.await
+			.unwrap();
+		let sha512 = openssl::sha::sha512(b"same");
+
+		let report = store.dedupe(&mods, LinkMode::Shared).await.unwrap();
+		assert_eq!(report.files, 2);
+		assert_eq!(report.saved_bytes, 4);
+		assert_eq!(store.references(&sha512), 2);
+		assert_eq!(
+			fs::read(path!(mods / "nested" / "two.jar")).await.unwrap(),
+			b"same"
+		);
+
+		// Linked files are not counted as saved again
+		let report = store.dedupe(&mods, LinkMode::Shared).await.unwrap();
+		assert_eq!(report.saved_bytes, 0);
+		assert_eq!(store.references(&sha512), 2);
+
+		// Without reflinks nothing is replaced, and only replaced files are
+		// referenced
+		let config = path!(data_dir / "instances" / "a" / "config");
+		fs::create_dir_all(&config).await.unwrap();
+		fs::write(path!(config / "mod.toml"), b"config")
+			.await
+			.unwrap();
+		let report = store.dedupe(&config, LinkMode::Private).await.unwrap();
+		assert_eq!(
+			store.references(&openssl::sha::sha512(b"config")),
+			report.files
+		);
+
+		drop(store);
+		fs::remove_dir_all(&data_dir).await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn collect_garbage() {
+		let data_dir = test_store_dir("gc");
+		let launcher = Launcher::new(&data_dir);
+		let mut store = Store::open(&launcher).await.unwrap();
+
+		let source = path!(data_dir / "source.jar");
+		fs::write(&source, b"mod").await.unwrap();
+		let sha512 = store.insert_file(&source).await.unwrap();
+		let kept = path!(data_dir / "instances" / "a" / "mod.jar");
+		let deleted = path!(data_dir / "instances" / "b" / "mod.jar");
+		store.link(&sha512, &kept, LinkMode::Private).await.unwrap();
+		store
+			.link(&sha512, &deleted, LinkMode::Private)
+			.await
+			.unwrap();
+		fs::remove_file(&deleted).await.unwrap();
+
+		let temp_dir = path!(store.dir() / "tmp");
+		fs::create_dir_all(&temp_dir).await.unwrap();
+		let fresh = path!(temp_dir / "fresh");
+		let stale = path!(temp_dir / "stale");
+		fs::write(&fresh, b"").await.unwrap();
+		fs::write(&stale, b"").await.unwrap();
+		std::fs::File::options()
+			.write(true)
+			.open(&stale)
+			.unwrap()
+			.set_modified(std::time::SystemTime::now() - 2 * TEMP_MAX_AGE)
+			.unwrap();
+
+		let report = store.collect_garbage().await.unwrap();
+		assert_eq!(report.dropped_references, 1);
+		assert_eq!(report.removed_objects, 0);
+		assert_eq!(store.references(&sha512), 1);
+		assert!(fresh.exists());
+		assert!(!stale.exists());
+
+		fs::write(&kept, b"edited").await.unwrap();
+		let report = store.collect_garbage().await.unwrap();
+		assert_eq!(report.dropped_references, 1);
+		assert_eq!(report.removed_objects, 1);
+		assert!(!store.object_path(&sha512).exists());
+
+		// The index was saved
+		drop(store);
+		let store = Store::open(&launcher).await.unwrap();
+		assert_eq!(store.references(&sha512), 0);
+
+		drop(store);
+		fs::remove_dir_all(&data_dir).await.unwrap();
+	}
+}
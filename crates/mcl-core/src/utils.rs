@@ -1,33 +1,268 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use std::time::Duration;
 
+use hex::FromHex;
+use openssl::hash::MessageDigest;
 use reqwest::IntoUrl;
-use serde::de::DeserializeOwned;
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-use crate::HTTP;
+use crate::error::{Error, ErrorKind, ResultExt};
 
-#[derive(Debug, Clone, Copy)]
+/// Expected hash of a file. Serialized as a single entry map from the
+/// algorithm to the hex digest, like `{"sha1": "…"}`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
 pub enum Hash {
-	Sha1([u8; 20]),
+	Sha1(#[serde(with = "hex")] [u8; 20]),
+	Sha256(#[serde(with = "hex")] [u8; 32]),
+	Sha512(#[serde(with = "hex")] [u8; 64]),
+	/// Only for sources that publish nothing stronger, like the Purpur API.
+	Md5(#[serde(with = "hex")] [u8; 16]),
+	#[serde(skip)]
 	None(),
 }
 
 impl Hash {
+	/// Parses a hex digest of the algorithm named like in [`Hash::algorithm`].
+	/// `None` for unknown algorithms or invalid digests.
+	#[must_use]
+	pub fn from_hex(algorithm: &str, digest: &str) -> Option<Hash> {
+		match algorithm {
+			"sha1" => <[u8; 20]>::from_hex(digest).ok().map(Hash::Sha1),
+			"sha256" => <[u8; 32]>::from_hex(digest).ok().map(Hash::Sha256),
+			"sha512" => <[u8; 64]>::from_hex(digest).ok().map(Hash::Sha512),
+			"md5" => <[u8; 16]>::from_hex(digest).ok().map(Hash::Md5),
+			_ => None,
+		}
+	}
+
+	#[must_use]
+	pub fn algorithm(&self) -> &'static str {
+		match self {
+			Hash::Sha1(_) => "sha1",
+			Hash::Sha256(_) => "sha256",
+			Hash::Sha512(_) => "sha512",
+			Hash::Md5(_) => "md5",
+			Hash::None() => "none",
+		}
+	}
+
+	#[must_use]
+	pub fn digest(&self) -> &[u8] {
+		match self {
+			Hash::Sha1(digest) => digest,
+			Hash::Sha256(digest) => digest,
+			Hash::Sha512(digest) => digest,
+			Hash::Md5(digest) => digest,
+			Hash::None() => &[],
+		}
+	}
+
+	fn message_digest(&self) -> Option<MessageDigest> {
+		match self {
+			Hash::Sha1(_) => Some(MessageDigest::sha1()),
+			Hash::Sha256(_) => Some(MessageDigest::sha256()),
+			Hash::Sha512(_) => Some(MessageDigest::sha512()),
+			Hash::Md5(_) => Some(MessageDigest::md5()),
+			Hash::None() => None,
+		}
+	}
+
 	#[must_use]
 	pub fn verify(self, data: &[u8]) -> bool {
-		match &self {
-			// Hash::Sha1(hash) => digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, data).as_ref()
-			// == *hash,
-			Hash::Sha1(hash) => openssl::sha::sha1(data) == *hash,
-			// Hash::Sha1(hash) => Sha1::from(data).digest().bytes() == *hash,
-			Hash::None() => true,
+		Hashes::from(self).verify(data)
+	}
+}
+
+/// Every hash a file is expected to have, all of which have to match. Empty if
+/// the source publishes none. Serialized as a map from the algorithm to the
+/// hex digest, like the `hashes` of Modrinth files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hashes(Vec<Hash>);
+
+impl Hashes {
+	#[must_use]
+	pub fn new(hashes: impl IntoIterator<Item = Hash>) -> Self {
+		Self(hashes.into_iter().collect())
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &Hash> {
+		self.0.iter()
+	}
+
+	pub fn push(&mut self, hash: Hash) {
+		self.0.push(hash);
+	}
+
+	/// The hash using `algorithm`, named like in [`Hash::algorithm`].
+	#[must_use]
+	pub fn get(&self, algorithm: &str) -> Option<Hash> {
+		self.0
+			.iter()
+			.copied()
+			.find(|hash| hash.algorithm() == algorithm)
+	}
+
+	#[must_use]
+	pub fn sha1(&self) -> Option<[u8; 20]> {
+		self.0.iter().find_map(|hash| {
+			match hash {
+				Hash::Sha1(sha1) => Some(*sha1),
+				_ => None,
+			}
+		})
+	}
+
+	pub fn hasher(&self) -> io::Result<Hasher> {
+		Ok(Hasher {
+			hashers: self
+				.0
+				.iter()
+				.filter_map(|hash| hash.message_digest().map(|digest| (*hash, digest)))
+				.map(|(hash, digest)| Ok((hash, openssl::hash::Hasher::new(digest)?)))
+				.collect::<io::Result<_>>()?,
+		})
+	}
+
+	#[must_use]
+	pub fn verify(&self, data: &[u8]) -> bool {
+		self.hasher()
+			.and_then(|mut hasher| {
+				hasher.update(data)?;
+				hasher.finish()
+			})
+			.unwrap_or(false)
+	}
+
+	/// Reads the file at `path` in chunks and checks it against every hash.
+	pub async fn verify_file(&self, path: impl AsRef<Path>) -> io::Result<bool> {
+		let mut file = fs::File::open(path).await?;
+		let mut hasher = self.hasher()?;
+		let mut buffer = vec![0; 64 * 1024];
+
+		loop {
+			let read = file.read(&mut buffer).await?;
+			if read == 0 {
+				break;
+			}
+			hasher.update(&buffer[..read])?;
+		}
+
+		hasher.finish()
+	}
+}
+
+impl From<Hash> for Hashes {
+	fn from(hash: Hash) -> Self {
+		Self(vec![hash])
+	}
+}
+
+impl From<Option<Hash>> for Hashes {
+	fn from(hash: Option<Hash>) -> Self {
+		Self(hash.into_iter().collect())
+	}
+}
+
+impl From<Vec<Hash>> for Hashes {
+	fn from(hashes: Vec<Hash>) -> Self {
+		Self(hashes)
+	}
+}
+
+impl Serialize for Hashes {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_map(
+			self.0
+				.iter()
+				.filter(|hash| !matches!(hash, Hash::None()))
+				.map(|hash| (hash.algorithm(), hex::encode(hash.digest()))),
+		)
+	}
+}
+
+impl<'de> Deserialize<'de> for Hashes {
+	/// Hashes using algorithms that are not supported are left out.
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let map = BTreeMap::<String, String>::deserialize(deserializer)?;
+		let mut hashes = Vec::new();
+
+		for (algorithm, digest) in map {
+			match Hash::from_hex(&algorithm, &digest) {
+				Some(hash) => hashes.push(hash),
+				None if matches!(algorithm.as_str(), "sha1" | "sha256" | "sha512" | "md5") => {
+					return Err(de::Error::custom(format!(
+						"invalid {algorithm} digest `{digest}`"
+					)));
+				},
+				None => {},
+			}
+		}
+
+		Ok(Self(hashes))
+	}
+}
+
+/// Hashes data fed to it in pieces, like a download as it streams in, to
+/// check it against [`Hashes`].
+pub struct Hasher {
+	hashers: Vec<(Hash, openssl::hash::Hasher)>,
+}
+
+impl Hasher {
+	pub fn update(&mut self, data: &[u8]) -> io::Result<()> {
+		for (_, hasher) in &mut self.hashers {
+			hasher.update(data)?;
+		}
+		Ok(())
+	}
+
+	/// Whether all data so far matches every expected hash.
+	pub fn finish(mut self) -> io::Result<bool> {
+		for (hash, hasher) in &mut self.hashers {
+			if *hasher.finish()? != *hash.digest() {
+				return Ok(false);
+			}
+		}
+		Ok(true)
+	}
+}
+
+/// The SHA-512 of the file at `path`, read in chunks.
+pub async fn sha512_file(path: impl AsRef<Path>) -> io::Result<[u8; 64]> {
+	let mut file = fs::File::open(path).await?;
+	let mut hasher = openssl::sha::Sha512::new();
+	let mut buffer = vec![0; 64 * 1024];
+
+	loop {
+		let read = file.read(&mut buffer).await?;
+		if read == 0 {
+			return Ok(hasher.finish());
 		}
+		hasher.update(&buffer[..read]);
 	}
 }
 
+/// Whether `path` is relative and made of plain names only, so joining it to a
+/// directory stays inside that directory.
+#[must_use]
+pub fn is_contained(path: &Path) -> bool {
+	path.components().next().is_some()
+		&& path
+			.components()
+			.all(|component| matches!(component, Component::Normal(_)))
+}
+
 pub async fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
 	if let Some(dir) = path.as_ref().parent() {
 		fs::create_dir_all(&dir).await?;
@@ -35,89 +270,56 @@ pub async fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Re
 	fs::write(&path, contents).await
 }
 
-#[derive(thiserror::Error, Debug)]
-pub enum RequestError {
-	#[error("{0}")]
-	Reqwest(
-		#[from]
-		#[backtrace]
-		reqwest::Error,
-	),
-	#[error("{0}")]
-	Serde(
-		#[from]
-		#[backtrace]
-		serde_json::Error,
-	),
-	#[error("{0}")]
-	Io(
-		#[from]
-		#[backtrace]
-		io::Error,
-	),
-	#[error("response hash does not match")]
-	HashMismatch(),
-	#[error("{0}")]
-	SystemTime(
-		#[from]
-		#[backtrace]
-		std::time::SystemTimeError,
-	),
-}
-
-// somehow split up verifier and downloader fns
-//
-
-// read file, verify hash, write file
-
-pub async fn verifier() {}
-
 pub async fn get_json<T: DeserializeOwned>(
+	http: &reqwest::Client,
 	url: impl IntoUrl,
-	path: impl AsRef<Path> + std::fmt::Debug,
-	hash: Option<Hash>,
+	path: impl AsRef<Path>,
+	hashes: impl Into<Hashes>,
 	valid_for: Option<Duration>,
-) -> Result<T, RequestError> {
+) -> Result<T, Error> {
+	let hashes = hashes.into();
 	let mut fallback_bytes: Option<Vec<u8>> = None;
 
 	if let Ok(metadata) = fs::metadata(&path).await {
-		let file_bytes = fs::read(&path).await?;
+		let file_bytes = fs::read(&path).await.with_path(&path)?;
 
-		if let Some(hash) = hash {
-			if hash.verify(&file_bytes) {
-				dbg!(path, "hash matches");
-				return Ok(serde_json::from_slice::<T>(&file_bytes)?);
+		if !hashes.is_empty() {
+			if hashes.verify(&file_bytes) {
+				return serde_json::from_slice::<T>(&file_bytes).with_path(path);
 			}
 		} else if let Some(valid_for) = valid_for {
-			dbg!(metadata.modified()?.elapsed()?, valid_for);
-			if metadata.modified()?.elapsed()? <= valid_for {
-				dbg!(path, "still valid");
-				return Ok(serde_json::from_slice::<T>(&file_bytes)?);
+			let age = metadata.modified()?.elapsed().unwrap_or_default();
+			if age <= valid_for {
+				return serde_json::from_slice::<T>(&file_bytes).with_path(path);
 			}
 		}
 
 		fallback_bytes = Some(file_bytes);
 	}
 
-	match HTTP.get(url).send().await {
+	let url = url.into_url()?;
+
+	// An error status falls back to the cached file like a failed request
+	match http
+		.get(url.clone())
+		.send()
+		.await
+		.and_then(reqwest::Response::error_for_status)
+	{
 		Ok(response) => {
 			let response_bytes = response.bytes().await?;
 
-			if let Some(hash) = hash {
-				if !hash.verify(&response_bytes) {
-					return Err(RequestError::HashMismatch());
-				}
+			if !hashes.verify(&response_bytes) {
+				return Err(Error::new(ErrorKind::HashMismatch)).with_url(url);
 			}
 
-			dbg!(&path, "downloaded");
-			fs::write(&path, &response_bytes).await?;
+			write(&path, &response_bytes).await.with_path(&path)?;
 
-			Ok(serde_json::from_slice::<T>(&response_bytes)?)
+			serde_json::from_slice::<T>(&response_bytes).with_url(url)
 		},
 		Err(err) => {
 			if let Some(fallback_bytes) = fallback_bytes {
-				dbg!(path, "fallback");
-				return Ok(serde_json::from_slice::<T>(&fallback_bytes)?);
+				return serde_json::from_slice::<T>(&fallback_bytes).with_path(path);
 			}
 
 			Err(err.into())
@@ -131,28 +333,67 @@ pub enum DownloadResult {
 	Skipped,
 }
 
+/// Downloads `url` to `path` unless a file matching `hashes` is already there.
+/// The response is hashed as it streams into a `.part` file next to `path`,
+/// which only replaces `path` once every hash matched.
 pub async fn download_file(
+	http: &reqwest::Client,
 	url: impl IntoUrl,
 	path: impl AsRef<Path>,
-	hash: Option<Hash>,
+	hashes: impl Into<Hashes>,
 	skip: bool,
-) -> Result<DownloadResult, RequestError> {
+) -> Result<DownloadResult, Error> {
 	if skip {
 		return Ok(DownloadResult::Skipped);
 	}
 
-	if let Some(ref hash) = hash {
-		if let Ok(file_bytes) = fs::read(&path).await {
-			if hash.verify(&file_bytes) {
-				return Ok(DownloadResult::Skipped);
-			};
+	let path = path.as_ref();
+	let hashes = hashes.into();
+	if !hashes.is_empty() && hashes.verify_file(path).await.unwrap_or(false) {
+		return Ok(DownloadResult::Skipped);
+	}
+
+	let url = url.into_url()?;
+	let mut response = http.get(url.clone()).send().await?.error_for_status()?;
+
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir).await.with_path(dir)?;
+	}
+	let part = part_path(path);
+	let mut file = fs::File::create(&part).await.with_path(&part)?;
+	let mut hasher = hashes.hasher()?;
+
+	let verified = async {
+		while let Some(chunk) = response.chunk().await? {
+			hasher.update(&chunk)?;
+			file.write_all(&chunk).await.with_path(&part)?;
 		}
+		file.flush().await.with_path(&part)?;
+		Ok::<_, Error>(hasher.finish()?)
 	}
+	.await;
 
-	let bytes = HTTP.get(url).send().await?.bytes().await?;
-	write(path, bytes).await?;
+	match verified {
+		Ok(true) => {
+			fs::rename(&part, path).await.with_path(path)?;
+			Ok(DownloadResult::Downloaded)
+		},
+		Ok(false) => {
+			let _ = fs::remove_file(&part).await;
+			Err(Error::new(ErrorKind::HashMismatch)).with_url(url)
+		},
+		Err(err) => {
+			let _ = fs::remove_file(&part).await;
+			Err(err)
+		},
+	}
+}
 
-	Ok(DownloadResult::Downloaded)
+/// `path` with `.part` appended, where downloads are written until verified.
+fn part_path(path: &Path) -> PathBuf {
+	let mut part = path.as_os_str().to_owned();
+	part.push(".part");
+	PathBuf::from(part)
 }
 
 pub fn replace_placeholders<H: std::hash::BuildHasher>(
@@ -186,3 +427,39 @@ pub fn replace_placeholders<H: std::hash::BuildHasher>(
 
 	Some(out.into_iter().collect())
 }
+
+/// Matches `text` against a glob `pattern` where `*` matches any sequence of
+/// characters and `?` matches a single character.
+#[must_use]
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let text: Vec<char> = text.chars().collect();
+
+	let (mut p, mut t) = (0, 0);
+	let mut backtrack: Option<(usize, usize)> = None;
+
+	while t < text.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				backtrack = Some((p, t));
+				p += 1;
+			},
+			Some(&char) if char == '?' || char == text[t] => {
+				p += 1;
+				t += 1;
+			},
+			_ => {
+				match backtrack {
+					Some((star, matched)) => {
+						p = star + 1;
+						t = matched + 1;
+						backtrack = Some((star, matched + 1));
+					},
+					None => return false,
+				}
+			},
+		}
+	}
+
+	pattern[p..].iter().all(|&char| char == '*')
+}